@@ -0,0 +1,54 @@
+//! Builds an Atom feed of only the Windows 11 announcements with the feed builders,
+//! instead of the feed written by `render::atom`.
+//!
+//! ```sh
+//! cargo run --example custom_feed -- page.html > windows11.xml
+//! ```
+
+use std::env;
+use std::fs;
+use unofficial_windows_message_center_rss::feed::{Category, Entry, Feed, Link, Person};
+use unofficial_windows_message_center_rss::parser::Parser;
+
+const SELF_URI: &str = "https://example.com/windows11.xml";
+
+fn main() {
+    let path = env::args().nth(1).expect("usage: custom_feed PAGE.html");
+    let html = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let articles = Parser::new(SELF_URI, "https://learn.microsoft.com").parse(&html);
+    let entries: Vec<Entry> = articles
+        .iter()
+        .filter(|a| a.categories.iter().any(|c| c.term == "windows-11"))
+        .map(|a| {
+            Entry::new(
+                format!("urn:example:windows-message-center:{}", a.id),
+                a.title.as_str().into(),
+                a.date.clone(),
+            )
+            .link(vec![Link::new().href(a.url.as_str()).rel(a.rel)])
+            .category(
+                a.categories
+                    .iter()
+                    .map(|c| Category::new(c.term.as_str()).label(c.label.as_str()))
+                    .collect(),
+            )
+            .summary(a.summary.as_str())
+            .content(a.body.clone())
+        })
+        .collect();
+    // 最も新しいお知らせの日時をフィードの更新日時にする
+    let updated = articles
+        .iter()
+        .map(|a| a.date.as_str())
+        .max()
+        .unwrap_or("1970-01-01T00:00:00Z");
+    let feed = Feed::new(
+        "urn:example:windows-message-center:windows-11".to_string(),
+        "Windows 11 announcements".to_string(),
+        updated.to_string(),
+    )
+    .author(vec![Person::new("Example IT")])
+    .link(vec![Link::new().href(SELF_URI).rel("self")])
+    .entry(entries);
+    println!("{}", feed.to_xml());
+}
@@ -0,0 +1,28 @@
+//! Parses a saved copy of the Windows message center and prints the announcements.
+//!
+//! ```sh
+//! curl -o page.html https://learn.microsoft.com/en-us/windows/release-health/windows-message-center
+//! cargo run --example parse_saved_page -- page.html
+//! ```
+
+use std::env;
+use std::fs;
+use unofficial_windows_message_center_rss::parser::Parser;
+
+fn main() {
+    let path = env::args()
+        .nth(1)
+        .expect("usage: parse_saved_page PAGE.html");
+    let html = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    // self_uriは自分のページを持たないお知らせのリンクになる
+    let parser = Parser::new(
+        "https://example.com/atom.xml",
+        "https://learn.microsoft.com",
+    );
+    for a in parser.parse(&html) {
+        let categories: Vec<&str> = a.categories.iter().map(|c| c.term.as_str()).collect();
+        println!("{}\t{}\t{}", a.date, a.id, a.title);
+        println!("\t{}", a.url);
+        println!("\t[{}] {}", categories.join(", "), a.summary);
+    }
+}
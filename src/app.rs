@@ -0,0 +1,390 @@
+use super::article::Article;
+use super::boilerplate::{self, Boilerplate};
+use super::cli::{self, BoilerplateList, Command, ParserConfig, Source};
+use super::config::Ini;
+use super::fleet::{self, Fleet};
+use super::parser::Parser;
+use super::render::{self, ContentType, FeedOptions, MESSAGE_CENTER_URL};
+use super::rules::Rules;
+use super::sanitize::Sanitizer;
+use super::state::State;
+use super::store::Store;
+use super::summary::Summarizer;
+use super::terminal::Terminal;
+use super::webhook::Webhooks;
+use super::{backfill, digest, markdown, search, server, warc};
+
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::Duration;
+
+fn now() -> String {
+    Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn write_stdout(s: &str) {
+    std::io::stdout().write_all(s.as_bytes()).unwrap();
+}
+
+fn open_store(path: &Path) -> Store {
+    Store::open(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn load_state(path: &Path) -> State {
+    open_store(path)
+        .load()
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn save_state(state: &State, path: &Path) {
+    open_store(path)
+        .save(state)
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+}
+
+fn load_fleet(path: &Path) -> Fleet {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| Fleet::parse(&s).map_err(String::from))
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn load_boilerplate(path: &Path) -> Boilerplate {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| Boilerplate::parse(&s).map_err(String::from))
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn load_sanitizer(path: &Path) -> Sanitizer {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| Sanitizer::parse(&s).map_err(String::from))
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+// 共通の設定ファイルからreadが必要なセクションを読む。
+// どの機能のものでもないセクションは書き間違いとして扱う
+fn load_settings<T>(path: &Path, read: impl FnOnce(&Ini) -> Result<T, String>) -> T {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| {
+            let ini = Ini::parse(&s)?;
+            ini.expect_kinds(&["rule NAME", "webhook NAME", "mail"])?;
+            read(&ini)
+        })
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn new_parser(self_uri: &str, config: &ParserConfig) -> Parser {
+    let mut parser = Parser::new(self_uri, "https://learn.microsoft.com");
+    if let Some(path) = &config.fleet {
+        parser = parser.fleet(load_fleet(path));
+    }
+    match &config.boilerplate {
+        Some(BoilerplateList::BuiltIn) => parser = parser.boilerplate(Boilerplate::default()),
+        Some(BoilerplateList::File(path)) => parser = parser.boilerplate(load_boilerplate(path)),
+        None => {}
+    }
+    if let Some(path) = &config.sanitizer {
+        parser = parser.sanitizer(load_sanitizer(path));
+    }
+    if let Some(length) = config.summary_length {
+        parser = parser.summarizer(Summarizer::new(length));
+    }
+    parser
+}
+
+fn build(
+    source: Source,
+    state_path: Option<&Path>,
+    ics_path: Option<&Path>,
+    settings_path: Option<&Path>,
+    options: FeedOptions,
+    config: &ParserConfig,
+) {
+    let self_uri = &source.self_uri;
+    let (rules, webhooks) = match settings_path {
+        Some(path) => load_settings(path, |ini| {
+            Ok((Rules::from_config(ini)?, Webhooks::from_config(ini)?))
+        }),
+        None => (Rules::default(), Webhooks::new(Vec::new())),
+    };
+    let doc = source.read_html().unwrap_or_else(|e| panic!("{}", e));
+    let mut articles = new_parser(self_uri, config).parse(&doc);
+    let store = state_path.map(|path| (path, open_store(path)));
+    let mut state = None;
+    if let Some((path, store)) = &store {
+        let mut s = store
+            .load()
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        s.merge(&articles, &now());
+        store
+            .save(&s)
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        // 保存した内容を読み直して出力する
+        let ids: Vec<&str> = articles.iter().map(|a| a.id.as_str()).collect();
+        articles = store
+            .articles(&ids)
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        state = Some(s);
+    }
+    // 保存する状態には関係のないお知らせも残し、出力だけを絞り込む
+    if config.fleet.is_some() {
+        articles.retain(fleet::is_relevant);
+    }
+    for (rule, count) in rules.rules().iter().zip(rules.apply(&mut articles)) {
+        eprintln!("rule [{}]: {} matched", rule.name, count);
+    }
+    if let (false, Some(state)) = (webhooks.is_empty(), state.as_mut()) {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap();
+        for e in webhooks.notify(&client, &articles, state) {
+            eprintln!("webhook failed: {}", e);
+        }
+        // 通知した記録を残す。お知らせは変わっていないので書き込まれない
+        if let Some((path, store)) = &store {
+            store
+                .save(state)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        }
+    }
+    if let Some(path) = ics_path {
+        let ics = render::ics(None, articles.clone(), &now()).to_ics();
+        fs::write(path, ics).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    }
+    let articles = options.group(articles);
+    write_stdout(&render::atom(self_uri, articles, now(), options.content_type).to_xml());
+}
+
+// 後から記録されたものほど新しいので、同じidは後のキャプチャで上書きする
+fn warc_parse(warc: &Path, self_uri: &str) {
+    let parser = Parser::new(self_uri, "https://learn.microsoft.com");
+    let mut merged = BTreeMap::new();
+    for (_, doc) in
+        warc::read_responses(warc, MESSAGE_CENTER_URL).unwrap_or_else(|e| panic!("{}", e))
+    {
+        for a in parser.parse(&doc) {
+            merged.insert(a.id.clone(), a);
+        }
+    }
+    let mut articles = merged.into_values().collect::<Vec<Article>>();
+    articles.sort_by(|a, b| b.date.cmp(&a.date));
+    write_stdout(&render::atom(self_uri, articles, now(), ContentType::Html).to_xml());
+}
+
+fn run_backfill(repo: &Path, file: &str, state_path: &Path) {
+    let mut state = load_state(state_path);
+    let summary = backfill::backfill(repo, file, &mut state).unwrap_or_else(|e| panic!("{}", e));
+    save_state(&state, state_path);
+    eprintln!(
+        "{} revisions ({} skipped): {} new, {} changed, {} announcements in total",
+        summary.revisions,
+        summary.skipped,
+        summary.new,
+        summary.changed,
+        state.articles.len()
+    );
+}
+
+fn learn_boilerplate(state_path: &Path, min_count: usize) {
+    let state = load_state(state_path);
+    let bodies = state.articles.values().map(|a| a.article.body.as_str());
+    let mut out = String::new();
+    for (phrase, count) in boilerplate::learn(bodies, min_count) {
+        out += &format!("# {} announcements\n{}\n", count, phrase);
+    }
+    write_stdout(&out);
+}
+
+fn export_markdown(state_path: &Path, dir: &Path) {
+    let state = load_state(state_path);
+    let summary = markdown::export(state.articles.values().map(|a| &a.article), dir)
+        .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+    for id in &summary.skipped {
+        eprintln!("skip {:?}: the id is not a valid file name", id);
+    }
+    eprintln!(
+        "{} written, {} unchanged",
+        summary.written, summary.unchanged
+    );
+}
+
+fn history(state_path: &Path, id: &str) {
+    let revisions = open_store(state_path)
+        .revisions(id)
+        .unwrap_or_else(|e| panic!("{}: {}", state_path.display(), e));
+    let mut out = String::new();
+    for r in revisions {
+        out += &format!("{}\t{}\t{}\n", r.seen_at, r.fingerprint, r.article.title);
+    }
+    write_stdout(&out);
+}
+
+#[cfg(feature = "serde")]
+fn parse_to_json(source: Source, ndjson: bool, config: &ParserConfig) {
+    use super::schema;
+    let doc = source.read_html().unwrap_or_else(|e| panic!("{}", e));
+    let articles = new_parser(&source.self_uri, config).parse(&doc);
+    if ndjson {
+        write_stdout(&schema::to_ndjson(&articles));
+    } else {
+        write_stdout(&(schema::to_json(&articles) + "\n"));
+    }
+}
+
+#[cfg(feature = "serde")]
+fn render_from_json(self_uri: &str, input: Option<&Path>, options: FeedOptions) {
+    use super::schema;
+    use std::io::Read;
+    let s = match input {
+        Some(path) => {
+            fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+        }
+        None => {
+            let mut s = String::new();
+            std::io::stdin()
+                .read_to_string(&mut s)
+                .unwrap_or_else(|e| panic!("stdin: {}", e));
+            s
+        }
+    };
+    let mut articles = schema::from_json(&s).unwrap_or_else(|e| panic!("{}", e));
+    articles.sort_by(|a, b| b.date.cmp(&a.date));
+    let articles = options.group(articles);
+    write_stdout(&render::atom(self_uri, articles, now(), options.content_type).to_xml());
+}
+
+fn run_search(state_path: &Path, query: search::Query, json: bool) {
+    let hits = open_store(state_path)
+        .search(&query)
+        .unwrap_or_else(|e| panic!("{}: {}", state_path.display(), e));
+    if json {
+        write_stdout(&(search::json(&hits).to_string() + "\n"));
+    } else {
+        let color = std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+        write_stdout(&search::text(&hits, color));
+    }
+}
+
+fn show(source: Source, since: Option<&str>, width: usize, config: &ParserConfig) {
+    let doc = source.read_html().unwrap_or_else(|e| panic!("{}", e));
+    let mut articles = new_parser(&source.self_uri, config).parse(&doc);
+    if let Some(since) = since {
+        articles.retain(|a| a.date.as_str() >= since);
+    }
+    if config.fleet.is_some() {
+        articles.retain(fleet::is_relevant);
+    }
+    // パイプやリダイレクトの先には色を付けない
+    let color = std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let terminal = Terminal::new(width).color(color);
+    write_stdout(&terminal.render(&articles, &chrono::Local));
+}
+
+fn send_digest(state_path: &Path, config_path: &Path, dry_run: bool) {
+    let config = load_settings(config_path, digest::MailConfig::from_config);
+    let mut state = load_state(state_path);
+    if dry_run {
+        let message = config
+            .message(&digest::pending(&state))
+            .unwrap_or_else(|e| panic!("{}", e));
+        std::io::stdout().write_all(&message.formatted()).unwrap();
+        return;
+    }
+    let count = digest::send(&config, &mut state).unwrap_or_else(|e| panic!("{}", e));
+    save_state(&state, state_path);
+    eprintln!("{} announcements sent", count);
+}
+
+// コマンドライン引数 (args[0]はプログラム名) のとおりに実行する
+pub fn run(args: Vec<String>) {
+    match cli::parse(args) {
+        Command::Build {
+            source,
+            state,
+            ics,
+            settings,
+            options,
+            config,
+        } => build(
+            source,
+            state.as_deref(),
+            ics.as_deref(),
+            settings.as_deref(),
+            options,
+            &config,
+        ),
+        Command::WarcParse { warc, self_uri } => warc_parse(&warc, &self_uri),
+        Command::Backfill { repo, file, state } => run_backfill(&repo, &file, &state),
+        Command::Serve {
+            listen,
+            interval,
+            source,
+            options,
+            settings,
+            config,
+        } => {
+            let rules = match settings {
+                Some(path) => load_settings(&path, Rules::from_config),
+                None => Rules::default(),
+            };
+            server::run(&listen, interval, source, options, rules, |self_uri| {
+                new_parser(self_uri, &config)
+            })
+            .unwrap_or_else(|e| panic!("{}", e))
+        }
+        Command::LearnBoilerplate { state, min_count } => learn_boilerplate(&state, min_count),
+        Command::ExportMarkdown { state, dir } => export_markdown(&state, &dir),
+        Command::History { state, id } => history(&state, &id),
+        Command::Search {
+            state,
+            query,
+            since,
+            until,
+            categories,
+            sort,
+            limit,
+            json,
+        } => {
+            let query = search::Query {
+                since,
+                until,
+                categories,
+                sort,
+                limit,
+                ..search::Query::new(&query).unwrap_or_else(|e| panic!("{}: {}", query, e))
+            };
+            run_search(&state, query, json)
+        }
+        #[cfg(feature = "serde")]
+        Command::Parse {
+            source,
+            ndjson,
+            config,
+        } => parse_to_json(source, ndjson, &config),
+        #[cfg(feature = "serde")]
+        Command::Render {
+            self_uri,
+            input,
+            options,
+        } => render_from_json(&self_uri, input.as_deref(), options),
+        Command::Digest {
+            state,
+            config,
+            dry_run,
+        } => send_digest(&state, &config, dry_run),
+        Command::Show {
+            source,
+            since,
+            width,
+            config,
+        } => show(source, since.as_deref(), width, &config),
+    }
+}
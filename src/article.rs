@@ -1,13 +1,75 @@
-#[derive(Debug)]
+//! The announcements read from the message center.
+
+use super::text;
+
+// serdeは&strのフィールドを入力から借りるものとみなし、Deserialize<'static>しか実装しなくなる。
+// 別名にしておけば、relはdeserialize_relで作るので任意の入力から読める
+type Rel = &'static str;
+
+/// A category such as the type or the product of an announcement.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Category {
+    /// The identifier of the category in its scheme, e.g. `windows-11`.
+    pub term: String,
+    /// The URI of the kind of category, e.g. the product or the relevance to a fleet.
+    pub scheme: String,
+    /// The human-readable name, e.g. `Windows 11`.
+    pub label: String,
+}
+impl Category {
+    /// Creates a category from its term, scheme and label.
+    pub fn new(term: String, scheme: String, label: String) -> Self {
+        Category {
+            term,
+            scheme,
+            label,
+        }
+    }
+}
+
+/// An announcement of the Windows message center.
+///
+/// `date` is an RFC 3339 date in UTC and `body` is sanitized HTML.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct Article {
+    /// The id of the announcement on the page, e.g. `3110`.
     pub id: String,
+    /// The page of the announcement, or the feed itself when it has none.
     pub url: String,
-    pub rel: &'static str,
+    /// `alternate` when `url` is the page of the announcement, `self` when it has none.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_rel"))]
+    pub rel: Rel,
+    /// The title with whitespace normalized.
     pub title: String,
+    /// The date of the announcement.
     pub date: String,
+    /// The body as HTML.
     pub body: String,
+    /// A plain-text summary made from the first sentences of the body.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub summary: String,
+    /// The type and products of the announcement.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub categories: Vec<Category>,
+    /// KB numbers without the `KB` prefix, e.g. `5027231`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kbs: Vec<String>,
+    /// OS builds, e.g. `22621.1848`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub builds: Vec<String>,
+    /// Windows versions, e.g. `22H2` or `1809`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub versions: Vec<String>,
+    /// CVE identifiers, e.g. `CVE-2022-37966`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cves: Vec<String>,
 }
 impl Article {
+    /// Creates an announcement without summary, categories or metadata.
     pub fn new(
         id: String,
         url: String,
@@ -23,6 +85,50 @@ impl Article {
             title,
             date,
             body,
+            summary: String::new(),
+            categories: Vec::new(),
+            kbs: Vec::new(),
+            builds: Vec::new(),
+            versions: Vec::new(),
+            cves: Vec::new(),
+        }
+    }
+    /// The body without markup, with whitespace normalized.
+    pub fn body_text(&self) -> String {
+        let text: String = scraper::Html::parse_fragment(&self.body)
+            .root_element()
+            .text()
+            .collect();
+        text::normalize(&text)
+    }
+}
+
+// 保存したリンクの関係を、Article::relの値に戻す
+pub(crate) fn static_rel(rel: &str) -> &'static str {
+    match rel {
+        "self" => "self",
+        _ => "alternate",
+    }
+}
+
+// KB番号やカテゴリの一覧に、まだないものだけを出現順に加える
+pub(crate) fn push_unique<T: PartialEq>(v: &mut Vec<T>, values: impl IntoIterator<Item = T>) {
+    for value in values {
+        if !v.contains(&value) {
+            v.push(value);
         }
     }
 }
+
+// relは&'static strなので、読み込んだ文字列を既知の値に置き換える
+#[cfg(feature = "serde")]
+fn deserialize_rel<'de, D: serde::Deserializer<'de>>(d: D) -> Result<&'static str, D::Error> {
+    let rel = <String as serde::Deserialize>::deserialize(d)?;
+    match rel.as_str() {
+        "self" | "alternate" => Ok(static_rel(&rel)),
+        _ => Err(serde::de::Error::unknown_variant(
+            &rel,
+            &["alternate", "self"],
+        )),
+    }
+}
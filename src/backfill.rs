@@ -0,0 +1,194 @@
+use super::feed_reader;
+use super::state::{Change, State};
+
+use chrono::{DateTime, Utc};
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, PartialEq)]
+pub struct Revision {
+    pub commit: String,
+    pub date: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub revisions: usize,
+    pub skipped: usize,
+    pub new: usize,
+    pub changed: usize,
+}
+
+fn git(repo: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// 古いものから順に返す
+pub fn revisions(repo: &Path, file: &str) -> io::Result<Vec<Revision>> {
+    let log = git(
+        repo,
+        &["log", "--reverse", "--format=%H%x09%cI", "--", file],
+    )?;
+    log.lines()
+        .map(|line| {
+            let (commit, date) = line
+                .split_once('\t')
+                .ok_or_else(|| io::Error::other(format!("unexpected git log output: {}", line)))?;
+            let date = DateTime::parse_from_rfc3339(date)
+                .map_err(|e| io::Error::other(format!("{}: {}", date, e)))?
+                .with_timezone(&Utc)
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string();
+            Ok(Revision {
+                commit: commit.to_string(),
+                date,
+            })
+        })
+        .collect()
+}
+
+pub fn backfill(repo: &Path, file: &str, state: &mut State) -> io::Result<Summary> {
+    let mut summary = Summary::default();
+    for revision in revisions(repo, file)? {
+        summary.revisions += 1;
+        let xml = git(repo, &["show", &format!("{}:{}", revision.commit, file)])?;
+        let articles = match feed_reader::parse_atom(&xml) {
+            Ok(articles) => articles,
+            Err(e) => {
+                eprintln!("skip {}: {}", revision.commit, e);
+                summary.skipped += 1;
+                continue;
+            }
+        };
+        for (_, change) in state.merge(&articles, &revision.date) {
+            match change {
+                Change::New => summary.new += 1,
+                Change::Changed => summary.changed += 1,
+            }
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::article::{Article, Category};
+    use crate::render;
+    use crate::store::Store;
+    use crate::testing;
+    use std::fs;
+
+    fn commit(repo: &Path, date: &str, message: &str) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(["commit", "-q", "-a", "-m", message])
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    fn feed(articles: Vec<Article>) -> String {
+        render::atom(
+            "https://example.com/atom.xml",
+            articles,
+            "2023-06-14T00:00:00Z".to_string(),
+            render::ContentType::Html,
+        )
+        .to_xml()
+    }
+    fn article(id: &str, title: &str) -> Article {
+        let mut a = Article {
+            title: title.to_string(),
+            ..testing::article(id, "<div>body</div>")
+        };
+        a.summary = "body".to_string();
+        a.categories = vec![Category {
+            term: "windows-11".to_string(),
+            scheme: "https://example.com/product".to_string(),
+            label: "Windows 11".to_string(),
+        }];
+        a.kbs = vec!["5027231".to_string()];
+        a.cves = vec!["CVE-2022-37966".to_string()];
+        a
+    }
+    #[test]
+    fn backfill_from_fixture_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        assert!(Command::new("git")
+            .args(["init", "-q"])
+            .arg(repo)
+            .status()
+            .unwrap()
+            .success());
+        fs::create_dir(repo.join("feed")).unwrap();
+        let path = repo.join("feed/atom10.xml");
+        fs::write(&path, feed(vec![article("1", "first")])).unwrap();
+        Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["add", "."])
+            .status()
+            .unwrap();
+        commit(repo, "2023-06-13T09:00:00+09:00", "1");
+        fs::write(&path, "not a feed <").unwrap();
+        commit(repo, "2023-06-14T09:00:00+09:00", "2");
+        fs::write(
+            &path,
+            feed(vec![article("2", "second"), article("1", "first, edited")]),
+        )
+        .unwrap();
+        commit(repo, "2023-06-15T09:00:00+09:00", "3");
+        fs::write(&path, feed(vec![article("2", "second")])).unwrap();
+        commit(repo, "2023-06-16T09:00:00+09:00", "4");
+
+        let revs = revisions(repo, "feed/atom10.xml").unwrap();
+        assert_eq!(revs.len(), 4);
+        assert_eq!(revs[0].date, "2023-06-13T00:00:00Z");
+
+        let mut state = State::default();
+        let summary = backfill(repo, "feed/atom10.xml", &mut state).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                revisions: 4,
+                skipped: 1,
+                new: 2,
+                changed: 1
+            }
+        );
+        let first = &state.articles["1"];
+        assert_eq!(first.article.title, "first, edited");
+        assert_eq!(first.first_seen, "2023-06-13T00:00:00Z");
+        assert_eq!(first.last_changed, "2023-06-15T00:00:00Z");
+        assert_eq!(state.articles["2"].first_seen, "2023-06-15T00:00:00Z");
+
+        // 要約や分類も含めて状態ファイルに残る
+        let store = Store::open_in_memory().unwrap();
+        store.save(&state).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.articles, state.articles);
+        let second = &loaded.articles["2"].article;
+        assert_eq!(second.summary, "body");
+        assert_eq!(second.categories.len(), 1);
+        assert_eq!(second.kbs, ["5027231"]);
+        assert_eq!(second.cves, ["CVE-2022-37966"]);
+    }
+}
@@ -0,0 +1,255 @@
+//! Removing the boilerplate repeated at the end of the announcements.
+
+use super::config::{self, ParseError};
+use super::text;
+
+use ego_tree::NodeId;
+use regex::Regex;
+use scraper::{Html, Node};
+use std::collections::{BTreeMap, BTreeSet};
+
+// ほぼ全てのお知らせの末尾に付いている定型文
+const DEFAULT_PHRASES: [&str; 2] = [
+    "To be informed about the latest updates and releases, follow us on Twitter *",
+    "Short on time? Watch our short *",
+];
+
+fn phrase_regex(phrase: &str) -> Result<Regex, regex::Error> {
+    let words: Vec<String> = phrase
+        .split_whitespace()
+        .map(|w| {
+            w.split('*')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join("(?s:.*)")
+        })
+        .collect();
+    Regex::new(&format!(r"(?i){}", words.join(r"\s+")))
+}
+
+fn is_blank(s: &str) -> bool {
+    s.chars().all(|c| {
+        c.is_whitespace() || matches!(c, '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{feff}')
+    })
+}
+
+fn text_of(html: &Html, id: NodeId) -> String {
+    html.tree
+        .get(id)
+        .unwrap()
+        .descendants()
+        .filter_map(|n| n.value().as_text())
+        .map(|t| &**t)
+        .collect()
+}
+
+// 子孫のテキストを連結した文字列の[start, end)にあたるノードを取り除く
+fn remove_span(html: &mut Html, id: NodeId, start: usize, end: usize) {
+    let children: Vec<NodeId> = html
+        .tree
+        .get(id)
+        .unwrap()
+        .children()
+        .map(|n| n.id())
+        .collect();
+    let mut offset = 0;
+    for child in children {
+        let len = text_of(html, child).len();
+        let (s, e) = (offset, offset + len);
+        offset = e;
+        if e <= start || end <= s {
+            continue;
+        }
+        let mut node = html.tree.get_mut(child).unwrap();
+        if start <= s && e <= end {
+            node.detach();
+        } else if let Node::Text(t) = node.value() {
+            let text = format!("{}{}", &t[..start.max(s) - s], &t[end.min(e) - s..]);
+            t.text = text.into();
+        } else {
+            remove_span(html, child, start.max(s) - s, end.min(e) - s);
+        }
+    }
+}
+
+// 中身のないdivやp (例: <div>&nbsp;&nbsp;</div>)
+fn remove_spacers(html: &mut Html) {
+    let candidates: Vec<NodeId> = html
+        .root_element()
+        .descendants()
+        .filter(|n| {
+            n.value()
+                .as_element()
+                .is_some_and(|e| matches!(e.name(), "div" | "p" | "span"))
+        })
+        .map(|n| n.id())
+        .collect();
+    // 内側の要素を先に消すと、それだけを含んでいた外側の要素も空になる
+    for id in candidates.into_iter().rev() {
+        let node = html.tree.get(id).unwrap();
+        let only_breaks = node
+            .descendants()
+            .skip(1)
+            .filter_map(|n| n.value().as_element())
+            .all(|e| e.name() == "br");
+        if only_breaks && is_blank(&text_of(html, id)) {
+            html.tree.get_mut(id).unwrap().detach();
+        }
+    }
+}
+
+/// Phrases removed from the bodies, with the paragraphs left empty by the removal.
+///
+/// The default list has the notes about Twitter and the short videos found at the end of
+/// almost every announcement.
+#[derive(Debug)]
+pub struct Boilerplate {
+    rules: Vec<Regex>,
+}
+impl Default for Boilerplate {
+    fn default() -> Self {
+        Self::parse(&DEFAULT_PHRASES.join("\n")).unwrap()
+    }
+}
+impl Boilerplate {
+    /// Reads one phrase per line. Differences in whitespace and case are ignored, and `*`
+    /// matches any text. Empty lines and lines starting with `#` are skipped.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let rules = s
+            .lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'))
+            .map(|(i, l)| phrase_regex(l).map_err(|e| config::error(i + 1, &e.to_string())))
+            .collect::<Result<_, _>>()?;
+        Ok(Boilerplate { rules })
+    }
+    /// Returns `body` without the phrases.
+    pub fn strip(&self, body: &str) -> String {
+        let mut html = Html::parse_fragment(body);
+        let blocks: Vec<NodeId> = html
+            .root_element()
+            .children()
+            .filter(|n| n.value().is_element())
+            .map(|n| n.id())
+            .collect();
+        for block in blocks {
+            let text = text_of(&html, block);
+            let mut spans: Vec<(usize, usize)> = self
+                .rules
+                .iter()
+                .filter_map(|r| r.find(&text))
+                .map(|m| (m.start(), m.end()))
+                .collect();
+            spans.sort();
+            // 後ろから消せば、前にある範囲の位置は変わらない
+            let mut merged: Vec<(usize, usize)> = Vec::new();
+            for (s, e) in spans {
+                match merged.last_mut() {
+                    Some(last) if s <= last.1 => last.1 = last.1.max(e),
+                    _ => merged.push((s, e)),
+                }
+            }
+            for (s, e) in merged.into_iter().rev() {
+                remove_span(&mut html, block, s, e);
+            }
+        }
+        remove_spacers(&mut html);
+        html.root_element().inner_html()
+    }
+}
+
+fn sentences(paragraph: &str) -> Vec<&str> {
+    let mut ret = Vec::new();
+    let mut begin = 0;
+    let mut prev = ' ';
+    for (i, c) in paragraph.char_indices() {
+        if c == ' ' && matches!(prev, '.' | '!' | '?') {
+            ret.push(&paragraph[begin..i]);
+            begin = i + 1;
+        }
+        prev = c;
+    }
+    ret.push(&paragraph[begin..]);
+    ret
+}
+
+// 多くのお知らせに繰り返し現れる段落や文を、出現したお知らせの数が多い順に返す
+pub(crate) fn learn<'a, I: IntoIterator<Item = &'a str>>(
+    bodies: I,
+    min_count: usize,
+) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for body in bodies {
+        let html = Html::parse_fragment(body);
+        let mut candidates = BTreeSet::new();
+        for block in html
+            .root_element()
+            .children()
+            .filter(|n| n.value().is_element())
+        {
+            let paragraph = text::normalize(&text_of(&html, block.id()));
+            for s in sentences(&paragraph) {
+                candidates.insert(s.to_string());
+            }
+            candidates.insert(paragraph);
+        }
+        for c in candidates {
+            // 短すぎるものは見出し等なので除く
+            if c.len() >= 20 {
+                *counts.entry(c).or_default() += 1;
+            }
+        }
+    }
+    let mut ret: Vec<(String, usize)> = counts
+        .into_iter()
+        .filter(|(_, n)| *n >= min_count)
+        .collect();
+    ret.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    const BODY: &str = r#"<div>
+    The update is now available. To learn more, see
+    <a href="https://aka.ms/x">the article</a>. To be informed about the latest updates and
+    releases, follow us on Twitter <a href="https://twitter.com/windowsupdate"><u>@WindowsUpdate</u></a>.
+</div><div>&nbsp;&nbsp;</div><div><strong>Highlights:&nbsp;</strong></div><div></div><div><span>&#8203;</span><br></div><div>
+    Short on time? Watch our short
+    <a href="https://youtu.be/x">Windows 11 update</a>
+    release notes video for this month's tips.
+</div>"#;
+    #[test]
+    fn strip_default() {
+        assert_eq!(
+            Boilerplate::default().strip(BODY),
+            r#"<div>
+    The update is now available. To learn more, see
+    <a href="https://aka.ms/x">the article</a>. </div><div><strong>Highlights:&nbsp;</strong></div>"#
+        );
+    }
+    #[test]
+    fn strip_configured() {
+        let b = Boilerplate::parse("# 見出しも消す\nhighlights:\n").unwrap();
+        let stripped = b.strip(BODY);
+        assert!(!stripped.contains("Highlights"));
+        assert!(stripped.contains("Short on time?"));
+        assert!(stripped.contains("follow us on Twitter"));
+        assert!(Boilerplate::parse("a(b").is_ok());
+    }
+    #[test]
+    fn learn_repeated() {
+        let bodies = [
+            "<div>First announcement body. Follow us on Twitter @WindowsUpdate.</div>",
+            "<div>Second announcement body.   Follow us on Twitter\n@WindowsUpdate.</div><div>&nbsp;</div>",
+            "<div>Short one.</div><div>Follow us on Twitter @WindowsUpdate.</div>",
+        ];
+        assert_eq!(
+            learn(bodies, 2),
+            [("Follow us on Twitter @WindowsUpdate.".to_string(), 3)]
+        );
+        assert_eq!(learn(bodies, 4), []);
+    }
+}
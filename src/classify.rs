@@ -0,0 +1,200 @@
+use super::article::{Article, Category};
+
+use regex::Regex;
+
+pub const TYPE_SCHEME: &str =
+    "https://github.com/yumetodo/unofficial-windows-message-center-rss/categories/type";
+pub const PRODUCT_SCHEME: &str =
+    "https://github.com/yumetodo/unofficial-windows-message-center-rss/categories/product";
+pub const TOPIC_SCHEME: &str =
+    "https://github.com/yumetodo/unofficial-windows-message-center-rss/categories/topic";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Title,
+    TitleAndBody,
+}
+
+#[derive(Debug)]
+struct Rule {
+    pattern: Regex,
+    target: Target,
+    scheme: &'static str,
+    term: &'static str,
+    label: &'static str,
+}
+
+#[derive(Debug)]
+pub struct Classifier {
+    rules: Vec<Rule>,
+}
+impl Default for Classifier {
+    fn default() -> Self {
+        let rules = [
+            // タイトルの先頭に付く"Take action:"等の定型句
+            (
+                r"^\s*Take action\s*:",
+                Target::Title,
+                TYPE_SCHEME,
+                "take-action",
+                "Take action",
+            ),
+            (
+                r"^\s*Reminder\s*:",
+                Target::Title,
+                TYPE_SCHEME,
+                "reminder",
+                "Reminder",
+            ),
+            (
+                r"^\s*Heads up\s*:",
+                Target::Title,
+                TYPE_SCHEME,
+                "heads-up",
+                "Heads up",
+            ),
+            (
+                r"^\s*Coming soon\s*:",
+                Target::Title,
+                TYPE_SCHEME,
+                "coming-soon",
+                "Coming soon",
+            ),
+            (
+                r"^\s*Resolved\s*:",
+                Target::Title,
+                TYPE_SCHEME,
+                "resolved",
+                "Resolved",
+            ),
+            (
+                r"\bWindows\s+11\b",
+                Target::TitleAndBody,
+                PRODUCT_SCHEME,
+                "windows-11",
+                "Windows 11",
+            ),
+            (
+                r"\bWindows\s+10\b",
+                Target::TitleAndBody,
+                PRODUCT_SCHEME,
+                "windows-10",
+                "Windows 10",
+            ),
+            (
+                r"\bWindows\s+Server\b",
+                Target::TitleAndBody,
+                PRODUCT_SCHEME,
+                "windows-server",
+                "Windows Server",
+            ),
+            (
+                r"\bsecurity\s+updates?\b",
+                Target::TitleAndBody,
+                TOPIC_SCHEME,
+                "security-update",
+                "Security update",
+            ),
+            (
+                r"\bend\s+of\s+(servicing|support)\b",
+                Target::TitleAndBody,
+                TOPIC_SCHEME,
+                "end-of-servicing",
+                "End of servicing",
+            ),
+            (
+                r"\bhardening\b",
+                Target::TitleAndBody,
+                TOPIC_SCHEME,
+                "hardening",
+                "Hardening",
+            ),
+            (
+                r"\bknown\s+issues?\b",
+                Target::TitleAndBody,
+                TOPIC_SCHEME,
+                "known-issue",
+                "Known issue",
+            ),
+        ];
+        Classifier {
+            rules: rules
+                .into_iter()
+                .map(|(pattern, target, scheme, term, label)| Rule {
+                    pattern: Regex::new(&format!("(?i){}", pattern)).unwrap(),
+                    target,
+                    scheme,
+                    term,
+                    label,
+                })
+                .collect(),
+        }
+    }
+}
+impl Classifier {
+    pub fn classify(&self, a: &Article) -> Vec<Category> {
+        let text = format!("{}\n{}", a.title, a.body_text());
+        self.rules
+            .iter()
+            .filter(|r| match r.target {
+                Target::Title => r.pattern.is_match(&a.title),
+                Target::TitleAndBody => r.pattern.is_match(&text),
+            })
+            .map(|r| Category {
+                term: r.term.to_string(),
+                scheme: r.scheme.to_string(),
+                label: r.label.to_string(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    fn terms(title: &str, body: &str) -> Vec<String> {
+        let a = Article {
+            title: title.to_string(),
+            ..testing::article("1", body)
+        };
+        Classifier::default()
+            .classify(&a)
+            .into_iter()
+            .map(|c| c.term)
+            .collect()
+    }
+    #[test]
+    fn classify() {
+        assert_eq!(
+            terms(
+                "Take action: June 2023 security update is now available",
+                "<div>for Windows 11 and all supported versions of Windows\n    10.</div>"
+            ),
+            ["take-action", "windows-11", "windows-10", "security-update"]
+        );
+        assert_eq!(
+            terms(
+                "Reminder: Security hardening changes for Netlogon and Kerberos coming in June and July 2023",
+                ""
+            ),
+            ["reminder", "hardening"]
+        );
+        assert_eq!(
+            terms(
+                "Heads up: End of support for Windows Server 2012",
+                "<p>known issues</p>"
+            ),
+            [
+                "heads-up",
+                "windows-server",
+                "end-of-servicing",
+                "known-issue"
+            ]
+        );
+        assert_eq!(
+            terms("Resolved: Windows 100 things", "Coming soon: Windows 10X"),
+            ["resolved"]
+        );
+    }
+}
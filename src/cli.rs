@@ -0,0 +1,778 @@
+use super::fetch::{FetchError, FetchMode, Fetcher};
+use super::filter;
+use super::grouping::{Grouping, Period};
+use super::render::{ContentType, FeedOptions, MESSAGE_CENTER_URL};
+use super::search::{self, Sort};
+use super::terminal;
+use super::warc::WarcWriter;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub struct Source {
+    pub self_uri: String,
+    pub path: Option<String>,
+    pub fetch_mode: FetchMode,
+    pub warc: Option<PathBuf>,
+}
+impl Source {
+    pub fn read_html(&self) -> Result<String, FetchError> {
+        match &self.path {
+            Some(p) => Ok(fs::read_to_string(p)?),
+            None => {
+                let mut fetcher = Fetcher::new(self.fetch_mode.clone())?;
+                if let Some(warc) = &self.warc {
+                    fetcher = fetcher.warc(WarcWriter::new(warc));
+                }
+                Ok(fetcher.fetch(MESSAGE_CENTER_URL)?.body)
+            }
+        }
+    }
+}
+
+// 取り除く定型文の一覧
+#[derive(Debug, PartialEq)]
+pub enum BoilerplateList {
+    // --boilerplate default
+    BuiltIn,
+    File(PathBuf),
+}
+
+// お知らせの解析方法を変える設定ファイル
+#[derive(Debug, Default, PartialEq)]
+pub struct ParserConfig {
+    pub fleet: Option<PathBuf>,
+    pub boilerplate: Option<BoilerplateList>,
+    pub sanitizer: Option<PathBuf>,
+    pub summary_length: Option<usize>,
+}
+
+pub enum Command {
+    Build {
+        source: Source,
+        state: Option<PathBuf>,
+        ics: Option<PathBuf>,
+        // [rule NAME]と[webhook NAME]を書く共通の設定ファイル。
+        // 通知済みのお知らせはstateに記録するので、stateが必要
+        settings: Option<PathBuf>,
+        options: FeedOptions,
+        config: ParserConfig,
+    },
+    WarcParse {
+        warc: PathBuf,
+        self_uri: String,
+    },
+    Backfill {
+        repo: PathBuf,
+        file: String,
+        state: PathBuf,
+    },
+    Serve {
+        listen: String,
+        interval: Duration,
+        // SELF_URIの代わりに、各フィードのURLの基点を受け取る
+        source: Source,
+        options: FeedOptions,
+        // [rule NAME]だけを使う。通知はしない
+        settings: Option<PathBuf>,
+        config: ParserConfig,
+    },
+    LearnBoilerplate {
+        state: PathBuf,
+        min_count: usize,
+    },
+    ExportMarkdown {
+        state: PathBuf,
+        dir: PathBuf,
+    },
+    History {
+        state: PathBuf,
+        id: String,
+    },
+    Search {
+        state: PathBuf,
+        query: String,
+        since: Option<String>,
+        until: Option<String>,
+        categories: Vec<String>,
+        sort: Sort,
+        limit: usize,
+        json: bool,
+    },
+    // 解析とフィードの出力を別のプロセスに分けられるように、お知らせをJSONで受け渡す
+    #[cfg(feature = "serde")]
+    Parse {
+        source: Source,
+        ndjson: bool,
+        config: ParserConfig,
+    },
+    #[cfg(feature = "serde")]
+    Render {
+        self_uri: String,
+        // なければ標準入力から読む
+        input: Option<PathBuf>,
+        options: FeedOptions,
+    },
+    Digest {
+        state: PathBuf,
+        config: PathBuf,
+        dry_run: bool,
+    },
+    Show {
+        source: Source,
+        // RFC 3339 (UTC)
+        since: Option<String>,
+        width: usize,
+        config: ParserConfig,
+    },
+}
+
+struct Args {
+    positional: Vec<String>,
+    values: Vec<(String, String)>,
+}
+impl Args {
+    fn new<I: Iterator<Item = String>>(mut it: I, value_flags: &[&str]) -> Option<Self> {
+        let mut positional = Vec::new();
+        let mut values = Vec::new();
+        while let Some(arg) = it.next() {
+            if value_flags.contains(&arg.as_str()) {
+                values.push((arg, it.next()?));
+            } else if arg.starts_with("--") {
+                return None;
+            } else {
+                positional.push(arg);
+            }
+        }
+        Some(Args { positional, values })
+    }
+    fn value(&self, flag: &str) -> Option<String> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(k, _)| k == flag)
+            .map(|(_, v)| v.clone())
+    }
+}
+
+fn usage(program: &str) -> String {
+    #[allow(unused_mut)]
+    let mut ret = format!(
+        "{program} SELF_URI [path] [--record DIR | --replay DIR] [--warc FILE] [--state FILE [--config FILE]] [--ics FILE] [--fleet FILE] [--boilerplate default|FILE] [--sanitizer FILE] [--summary-length N] [--content-type html|xhtml] [--group day|week [--time-zone TZ]]
+{program} warc-parse WARC SELF_URI
+{program} backfill REPO FILE STATE
+{program} serve --listen ADDR [--interval SECONDS] BASE_URL [path] [--record DIR | --replay DIR] [--warc FILE] [--config FILE] [--fleet FILE] [--boilerplate default|FILE] [--sanitizer FILE] [--summary-length N] [--content-type html|xhtml] [--group day|week [--time-zone TZ]]
+{program} learn-boilerplate STATE [--min-count N]
+{program} export-markdown STATE DIR
+{program} history STATE ID
+{program} search STATE QUERY... [--since DATE] [--until DATE] [--category TERMS] [--sort relevance|date] [--limit N] [--format text|json]
+{program} digest STATE CONFIG [--dry-run]
+{program} show [path] [--record DIR | --replay DIR] [--warc FILE] [--since DATE] [--width N] [--fleet FILE] [--boilerplate default|FILE] [--sanitizer FILE] [--summary-length N]"
+    );
+    #[cfg(feature = "serde")]
+    {
+        ret += &format!(
+            "
+{program} parse SELF_URI [path] [--record DIR | --replay DIR] [--warc FILE] [--fleet FILE] [--boilerplate default|FILE] [--sanitizer FILE] [--summary-length N] [--ndjson]
+{program} render SELF_URI [FILE] [--content-type html|xhtml] [--group day|week [--time-zone TZ]]"
+        );
+    }
+    ret
+}
+
+fn parse_source(args: &Args) -> Option<Source> {
+    match &args.positional[..] {
+        [self_uri] => source(self_uri.clone(), None, args),
+        [self_uri, path] => source(self_uri.clone(), Some(path.clone()), args),
+        _ => None,
+    }
+}
+
+fn source(self_uri: String, path: Option<String>, args: &Args) -> Option<Source> {
+    let fetch_mode = match (args.value("--record"), args.value("--replay")) {
+        (Some(_), Some(_)) => return None,
+        (Some(dir), None) => FetchMode::Record(dir.into()),
+        (None, Some(dir)) => FetchMode::Replay(dir.into()),
+        (None, None) => FetchMode::Web,
+    };
+    Some(Source {
+        self_uri,
+        path,
+        fetch_mode,
+        warc: args.value("--warc").map(PathBuf::from),
+    })
+}
+
+fn parse_config(args: &Args) -> Option<ParserConfig> {
+    let summary_length = match args.value("--summary-length") {
+        Some(s) => Some(s.parse().ok()?),
+        None => None,
+    };
+    Some(ParserConfig {
+        fleet: args.value("--fleet").map(PathBuf::from),
+        boilerplate: args.value("--boilerplate").map(|s| match s.as_str() {
+            "default" => BoilerplateList::BuiltIn,
+            _ => BoilerplateList::File(s.into()),
+        }),
+        sanitizer: args.value("--sanitizer").map(PathBuf::from),
+        summary_length,
+    })
+}
+
+fn parse_feed_options(args: &Args) -> Option<FeedOptions> {
+    let content_type = match args.value("--content-type") {
+        Some(s) => s.parse().ok()?,
+        None => ContentType::default(),
+    };
+    let tz = match args.value("--time-zone") {
+        Some(s) => s.parse().ok()?,
+        None => chrono_tz::UTC,
+    };
+    let grouping = match args.value("--group") {
+        Some(s) => Some(Grouping::new(Period::parse(&s)?, tz)),
+        // 期間でまとめないときは時間帯を使わない
+        None if args.value("--time-zone").is_some() => return None,
+        None => None,
+    };
+    Some(FeedOptions {
+        content_type,
+        grouping,
+    })
+}
+
+fn parse_command(args: &[String]) -> Option<Command> {
+    let mut it = args.iter().skip(1).cloned();
+    match args.get(1).map(String::as_str) {
+        Some("warc-parse") => {
+            it.next();
+            let args = Args::new(it, &[])?;
+            match &args.positional[..] {
+                [warc, self_uri] => Some(Command::WarcParse {
+                    warc: warc.into(),
+                    self_uri: self_uri.clone(),
+                }),
+                _ => None,
+            }
+        }
+        Some("backfill") => {
+            it.next();
+            let args = Args::new(it, &[])?;
+            match &args.positional[..] {
+                [repo, file, state] => Some(Command::Backfill {
+                    repo: repo.into(),
+                    file: file.clone(),
+                    state: state.into(),
+                }),
+                _ => None,
+            }
+        }
+        Some("serve") => {
+            it.next();
+            let args = Args::new(
+                it,
+                &[
+                    "--listen",
+                    "--interval",
+                    "--record",
+                    "--replay",
+                    "--warc",
+                    "--config",
+                    "--fleet",
+                    "--boilerplate",
+                    "--sanitizer",
+                    "--summary-length",
+                    "--content-type",
+                    "--group",
+                    "--time-zone",
+                ],
+            )?;
+            let interval = match args.value("--interval") {
+                Some(s) => s.parse().ok().filter(|n| *n > 0)?,
+                None => 3600,
+            };
+            Some(Command::Serve {
+                listen: args.value("--listen")?,
+                interval: Duration::from_secs(interval),
+                source: parse_source(&args)?,
+                options: parse_feed_options(&args)?,
+                settings: args.value("--config").map(PathBuf::from),
+                config: parse_config(&args)?,
+            })
+        }
+        Some("learn-boilerplate") => {
+            it.next();
+            let args = Args::new(it, &["--min-count"])?;
+            let min_count = match args.value("--min-count") {
+                Some(s) => s.parse().ok().filter(|n| *n > 0)?,
+                None => 3,
+            };
+            match &args.positional[..] {
+                [state] => Some(Command::LearnBoilerplate {
+                    state: state.into(),
+                    min_count,
+                }),
+                _ => None,
+            }
+        }
+        Some("export-markdown") => {
+            it.next();
+            let args = Args::new(it, &[])?;
+            match &args.positional[..] {
+                [state, dir] => Some(Command::ExportMarkdown {
+                    state: state.into(),
+                    dir: dir.into(),
+                }),
+                _ => None,
+            }
+        }
+        Some("history") => {
+            it.next();
+            let args = Args::new(it, &[])?;
+            match &args.positional[..] {
+                [state, id] => Some(Command::History {
+                    state: state.into(),
+                    id: id.clone(),
+                }),
+                _ => None,
+            }
+        }
+        Some("search") => {
+            it.next();
+            let args = Args::new(
+                it,
+                &[
+                    "--since",
+                    "--until",
+                    "--category",
+                    "--sort",
+                    "--limit",
+                    "--format",
+                ],
+            )?;
+            // 引用符で囲まなくても、残りの引数をまとめて検索式にする
+            let (state, query) = args.positional.split_first()?;
+            if query.is_empty() {
+                return None;
+            }
+            let since = match args.value("--since") {
+                Some(s) => Some(filter::parse_since(&s)?),
+                None => None,
+            };
+            let until = match args.value("--until") {
+                Some(s) => Some(search::parse_until(&s)?),
+                None => None,
+            };
+            let categories = args
+                .value("--category")
+                .map(|s| {
+                    s.split(',')
+                        .map(|s| s.trim().to_lowercase())
+                        .filter(|s| !s.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let sort = match args.value("--sort") {
+                Some(s) => Sort::parse(&s)?,
+                None => Sort::default(),
+            };
+            let limit = match args.value("--limit") {
+                Some(s) => s.parse().ok().filter(|n| *n > 0)?,
+                None => 20,
+            };
+            let json = match args.value("--format").as_deref() {
+                None | Some("text") => false,
+                Some("json") => true,
+                Some(_) => return None,
+            };
+            Some(Command::Search {
+                state: state.into(),
+                query: query.join(" "),
+                since,
+                until,
+                categories,
+                sort,
+                limit,
+                json,
+            })
+        }
+        #[cfg(feature = "serde")]
+        Some("parse") => {
+            it.next();
+            // --ndjsonは値を取らないので、Argsに渡す前に取り除く
+            let (flags, rest): (Vec<String>, Vec<String>) = it.partition(|a| a == "--ndjson");
+            let args = Args::new(
+                rest.into_iter(),
+                &[
+                    "--record",
+                    "--replay",
+                    "--warc",
+                    "--fleet",
+                    "--boilerplate",
+                    "--sanitizer",
+                    "--summary-length",
+                ],
+            )?;
+            Some(Command::Parse {
+                source: parse_source(&args)?,
+                ndjson: !flags.is_empty(),
+                config: parse_config(&args)?,
+            })
+        }
+        #[cfg(feature = "serde")]
+        Some("render") => {
+            it.next();
+            let args = Args::new(it, &["--content-type", "--group", "--time-zone"])?;
+            let (self_uri, input) = match &args.positional[..] {
+                [self_uri] => (self_uri.clone(), None),
+                [self_uri, input] => (self_uri.clone(), Some(input.into())),
+                _ => return None,
+            };
+            Some(Command::Render {
+                self_uri,
+                input,
+                options: parse_feed_options(&args)?,
+            })
+        }
+        Some("digest") => {
+            it.next();
+            // --dry-runは値を取らないので、Argsに渡す前に取り除く
+            let (flags, rest): (Vec<String>, Vec<String>) = it.partition(|a| a == "--dry-run");
+            let args = Args::new(rest.into_iter(), &[])?;
+            match &args.positional[..] {
+                [state, config] => Some(Command::Digest {
+                    state: state.into(),
+                    config: config.into(),
+                    dry_run: !flags.is_empty(),
+                }),
+                _ => None,
+            }
+        }
+        Some("show") => {
+            it.next();
+            let args = Args::new(
+                it,
+                &[
+                    "--record",
+                    "--replay",
+                    "--warc",
+                    "--since",
+                    "--width",
+                    "--fleet",
+                    "--boilerplate",
+                    "--sanitizer",
+                    "--summary-length",
+                ],
+            )?;
+            // フィードを出力しないので、SELF_URIの代わりにメッセージセンターのURLを使う
+            let source = match &args.positional[..] {
+                [] => source(MESSAGE_CENTER_URL.to_string(), None, &args)?,
+                [path] => source(MESSAGE_CENTER_URL.to_string(), Some(path.clone()), &args)?,
+                _ => return None,
+            };
+            let since = match args.value("--since") {
+                Some(s) => Some(filter::parse_since(&s)?),
+                None => None,
+            };
+            let width = match args.value("--width") {
+                Some(s) => s.parse().ok().filter(|n| *n > 0)?,
+                None => terminal::DEFAULT_WIDTH,
+            };
+            Some(Command::Show {
+                source,
+                since,
+                width,
+                config: parse_config(&args)?,
+            })
+        }
+        _ => {
+            let args = Args::new(
+                it,
+                &[
+                    "--record",
+                    "--replay",
+                    "--warc",
+                    "--state",
+                    "--ics",
+                    "--config",
+                    "--fleet",
+                    "--boilerplate",
+                    "--sanitizer",
+                    "--summary-length",
+                    "--content-type",
+                    "--group",
+                    "--time-zone",
+                ],
+            )?;
+            let state = args.value("--state").map(PathBuf::from);
+            let settings = args.value("--config").map(PathBuf::from);
+            if settings.is_some() && state.is_none() {
+                return None;
+            }
+            Some(Command::Build {
+                source: parse_source(&args)?,
+                state,
+                ics: args.value("--ics").map(PathBuf::from),
+                settings,
+                options: parse_feed_options(&args)?,
+                config: parse_config(&args)?,
+            })
+        }
+    }
+}
+
+pub fn parse(args: Vec<String>) -> Command {
+    parse_command(&args).unwrap_or_else(|| panic!("{}", usage(&args[0])))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+    #[test]
+    fn parse_build() {
+        match parse(args(
+            "rss https://example.com/atom.xml page.html --replay rec --ics deadlines.ics",
+        )) {
+            Command::Build {
+                source,
+                state,
+                ics,
+                settings,
+                options,
+                config,
+            } => {
+                assert_eq!(source.self_uri, "https://example.com/atom.xml");
+                assert_eq!(source.path.as_deref(), Some("page.html"));
+                assert_eq!(source.fetch_mode, FetchMode::Replay("rec".into()));
+                assert_eq!(source.warc, None);
+                assert_eq!(state, None);
+                assert_eq!(ics, Some(PathBuf::from("deadlines.ics")));
+                assert_eq!(settings, None);
+                assert_eq!(options, FeedOptions::default());
+                assert_eq!(config, ParserConfig::default());
+            }
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn parse_warc_parse() {
+        match parse(args(
+            "rss warc-parse capture.warc https://example.com/atom.xml",
+        )) {
+            Command::WarcParse { warc, self_uri } => {
+                assert_eq!(warc, PathBuf::from("capture.warc"));
+                assert_eq!(self_uri, "https://example.com/atom.xml");
+            }
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn parse_serve() {
+        match parse(args(
+            "rss serve --listen 127.0.0.1:8080 --interval 600 http://localhost:8080 --config rss.ini --fleet fleet.ini --boilerplate rules.txt --sanitizer allow.txt --summary-length 120 --content-type xhtml --group week --time-zone Asia/Tokyo",
+        )) {
+            Command::Serve {
+                listen,
+                interval,
+                source,
+                options,
+                settings,
+                config,
+            } => {
+                assert_eq!(settings, Some(PathBuf::from("rss.ini")));
+                assert_eq!(options.content_type, ContentType::Xhtml);
+                assert_eq!(
+                    options.grouping,
+                    Some(Grouping::new(Period::Week, chrono_tz::Asia::Tokyo))
+                );
+                assert_eq!(listen, "127.0.0.1:8080");
+                assert_eq!(interval, Duration::from_secs(600));
+                assert_eq!(source.self_uri, "http://localhost:8080");
+                assert_eq!(source.fetch_mode, FetchMode::Web);
+                assert_eq!(config.fleet, Some(PathBuf::from("fleet.ini")));
+                assert_eq!(
+                    config.boilerplate,
+                    Some(BoilerplateList::File(PathBuf::from("rules.txt")))
+                );
+                assert_eq!(config.sanitizer, Some(PathBuf::from("allow.txt")));
+                assert_eq!(config.summary_length, Some(120));
+            }
+            _ => panic!(),
+        }
+        assert!(parse_command(&args("rss serve http://localhost:8080")).is_none());
+        assert!(parse_command(&args("rss serve --listen :80 x --content-type text")).is_none());
+        assert!(parse_command(&args("rss serve --listen :80 --interval 0 x")).is_none());
+        assert!(parse_command(&args("rss serve --listen :80 x --group month")).is_none());
+        assert!(parse_command(&args(
+            "rss serve --listen :80 x --group day --time-zone Mars"
+        ))
+        .is_none());
+        assert!(parse_command(&args("rss serve --listen :80 x --time-zone UTC")).is_none());
+    }
+    #[test]
+    fn parse_learn_boilerplate() {
+        match parse(args("rss learn-boilerplate state.db --min-count 10")) {
+            Command::LearnBoilerplate { state, min_count } => {
+                assert_eq!(state, PathBuf::from("state.db"));
+                assert_eq!(min_count, 10);
+            }
+            _ => panic!(),
+        }
+        assert!(parse_command(&args("rss learn-boilerplate")).is_none());
+        assert!(parse_command(&args("rss learn-boilerplate s --min-count 0")).is_none());
+    }
+    #[test]
+    fn parse_export_markdown() {
+        match parse(args("rss export-markdown state.db kb/announcements")) {
+            Command::ExportMarkdown { state, dir } => {
+                assert_eq!(state, PathBuf::from("state.db"));
+                assert_eq!(dir, PathBuf::from("kb/announcements"));
+            }
+            _ => panic!(),
+        }
+        assert!(parse_command(&args("rss export-markdown state.db")).is_none());
+    }
+    #[test]
+    fn parse_search() {
+        match parse(args(
+            "rss search state.db Kerberos -Netlogon --since 2022-01-01 --until 2022-12-31 --category reminder,Windows-11 --sort date --limit 5 --format json",
+        )) {
+            Command::Search {
+                state,
+                query,
+                since,
+                until,
+                categories,
+                sort,
+                limit,
+                json,
+            } => {
+                assert_eq!(state, PathBuf::from("state.db"));
+                assert_eq!(query, "Kerberos -Netlogon");
+                assert_eq!(since.as_deref(), Some("2022-01-01T00:00:00Z"));
+                assert_eq!(until.as_deref(), Some("2022-12-31T23:59:59Z"));
+                assert_eq!(categories, ["reminder", "windows-11"]);
+                assert_eq!(sort, Sort::Date);
+                assert_eq!(limit, 5);
+                assert!(json);
+            }
+            _ => panic!(),
+        }
+        assert!(parse_command(&args("rss search state.db")).is_none());
+        assert!(parse_command(&args("rss search state.db x --sort rank")).is_none());
+        assert!(parse_command(&args("rss search state.db x --format xml")).is_none());
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_parse_and_render() {
+        match parse(args(
+            "rss parse https://example.com/atom.xml page.html --ndjson --summary-length 80",
+        )) {
+            Command::Parse {
+                source,
+                ndjson,
+                config,
+            } => {
+                assert_eq!(source.path.as_deref(), Some("page.html"));
+                assert!(ndjson);
+                assert_eq!(config.summary_length, Some(80));
+            }
+            _ => panic!(),
+        }
+        match parse(args(
+            "rss render https://example.com/atom.xml --content-type xhtml",
+        )) {
+            Command::Render {
+                self_uri,
+                input,
+                options,
+            } => {
+                assert_eq!(self_uri, "https://example.com/atom.xml");
+                assert_eq!(input, None);
+                assert_eq!(options.content_type, ContentType::Xhtml);
+            }
+            _ => panic!(),
+        }
+        assert!(parse_command(&args("rss render")).is_none());
+    }
+    #[test]
+    fn parse_history() {
+        match parse(args("rss history state.db 3110")) {
+            Command::History { state, id } => {
+                assert_eq!(state, PathBuf::from("state.db"));
+                assert_eq!(id, "3110");
+            }
+            _ => panic!(),
+        }
+        assert!(parse_command(&args("rss history state.db")).is_none());
+    }
+    #[test]
+    fn parse_digest() {
+        match parse(args("rss digest state.db mail.conf --dry-run")) {
+            Command::Digest {
+                state,
+                config,
+                dry_run,
+            } => {
+                assert_eq!(state, PathBuf::from("state.db"));
+                assert_eq!(config, PathBuf::from("mail.conf"));
+                assert!(dry_run);
+            }
+            _ => panic!(),
+        }
+        assert!(matches!(
+            parse(args("rss digest state.db mail.conf")),
+            Command::Digest { dry_run: false, .. }
+        ));
+        assert!(parse_command(&args("rss digest state.db")).is_none());
+    }
+    #[test]
+    fn parse_show() {
+        match parse(args(
+            "rss show page.html --since 2023-06-01 --width 100 --boilerplate default",
+        )) {
+            Command::Show {
+                source,
+                since,
+                width,
+                config,
+            } => {
+                assert_eq!(source.self_uri, MESSAGE_CENTER_URL);
+                assert_eq!(source.path.as_deref(), Some("page.html"));
+                assert_eq!(since.as_deref(), Some("2023-06-01T00:00:00Z"));
+                assert_eq!(width, 100);
+                assert_eq!(config.boilerplate, Some(BoilerplateList::BuiltIn));
+            }
+            _ => panic!(),
+        }
+        match parse(args("rss show")) {
+            Command::Show {
+                source,
+                since,
+                width,
+                ..
+            } => {
+                assert_eq!(source.path, None);
+                assert_eq!(source.fetch_mode, FetchMode::Web);
+                assert_eq!(since, None);
+                assert_eq!(width, terminal::DEFAULT_WIDTH);
+            }
+            _ => panic!(),
+        }
+        assert!(parse_command(&args("rss show --since yesterday")).is_none());
+        assert!(parse_command(&args("rss show a b")).is_none());
+    }
+    #[test]
+    fn reject_invalid() {
+        assert!(parse_command(&args("rss")).is_none());
+        assert!(parse_command(&args("rss a --config rss.ini")).is_none());
+        assert!(parse_command(&args("rss a --state state.db --webhooks hooks.ini")).is_none());
+        assert!(parse_command(&args("rss a --record x --replay y")).is_none());
+        assert!(parse_command(&args("rss a --unknown")).is_none());
+        assert!(parse_command(&args("rss a --warc")).is_none());
+        assert!(parse_command(&args("rss a --summary-length long")).is_none());
+    }
+}
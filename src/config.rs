@@ -0,0 +1,246 @@
+// 設定ファイルに共通の書式
+// #から始まる行はコメント。[name]から始まるセクションごとに key = value を並べ、
+// 最初のセクションより前に書いたものはファイル全体の設定になる
+
+use std::fmt;
+
+/// The error of reading a boilerplate list, an allow-list of the sanitizer or a fleet profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    line: Option<usize>,
+    message: String,
+}
+impl ParseError {
+    // 特定の行によらないエラー
+    pub(crate) fn new<S: Into<String>>(message: S) -> Self {
+        ParseError {
+            line: None,
+            message: message.into(),
+        }
+    }
+    /// The line (starting at 1) with the error, if the error is about one line.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+    /// The description of the error without the line number.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+impl std::error::Error for ParseError {}
+// 文字列のエラーを返す設定 (rules等) からも?で使えるように
+impl From<ParseError> for String {
+    fn from(e: ParseError) -> Self {
+        e.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub line: usize,
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub line: usize,
+    pub name: String,
+    pub entries: Vec<Entry>,
+}
+impl Section {
+    // 同じキーが何度も書かれていれば最後のもの
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.key == key)
+            .map(|e| e.value.as_str())
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Ini {
+    pub entries: Vec<Entry>,
+    pub sections: Vec<Section>,
+}
+impl Ini {
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut ret = Ini::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                ret.sections.push(Section {
+                    line: i + 1,
+                    name: name.trim().to_string(),
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(i + 1, "expected key = value"))?;
+            let entry = Entry {
+                line: i + 1,
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+            };
+            match ret.sections.last_mut() {
+                Some(section) => section.entries.push(entry),
+                None => ret.entries.push(entry),
+            }
+        }
+        Ok(ret)
+    }
+    // セクションだけを書くファイルで、最初のセクションより前に設定があればエラーにする
+    pub fn sections_only(self, what: &str) -> Result<Vec<Section>, ParseError> {
+        match self.entries.first() {
+            Some(e) => Err(error(
+                e.line,
+                &format!("expected [{}] before settings", what),
+            )),
+            None => Ok(self.sections),
+        }
+    }
+    // [KIND NAME]のセクションを、NAMEとともに返す
+    pub fn sections_of<'a>(
+        &'a self,
+        kind: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a Section)> + 'a {
+        self.sections.iter().filter_map(move |s| {
+            let (k, name) = s.name.split_once(char::is_whitespace)?;
+            (k == kind).then(|| (name.trim(), s))
+        })
+    }
+    // 名前のない[KIND]のセクション
+    pub fn section(&self, kind: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == kind)
+    }
+    // どの機能にも使われないセクションや設定は、書き間違いとして扱う。
+    // kindsは名前を付けて何度も書く"rule NAME"か、1度だけ書く"mail"
+    pub fn expect_kinds(&self, kinds: &[&str]) -> Result<(), ParseError> {
+        if let Some(e) = self.entries.first() {
+            return Err(error(e.line, &format!("unknown key: {}", e.key)));
+        }
+        let expected: Vec<String> = kinds.iter().map(|k| format!("[{}]", k)).collect();
+        let expected = match expected.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+            None => String::new(),
+        };
+        for (i, s) in self.sections.iter().enumerate() {
+            let known = kinds.iter().any(|k| match k.split_once(' ') {
+                Some((kind, _)) => s
+                    .name
+                    .split_once(char::is_whitespace)
+                    .is_some_and(|(k, name)| k == kind && !name.trim().is_empty()),
+                None => s.name == *k,
+            });
+            if !known {
+                return Err(error(
+                    s.line,
+                    &format!("expected {}: [{}]", expected, s.name),
+                ));
+            }
+            if !s.name.contains(char::is_whitespace)
+                && self.sections[..i].iter().any(|t| t.name == s.name)
+            {
+                return Err(error(s.line, &format!("[{}] is written twice", s.name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn error(line: usize, message: &str) -> ParseError {
+    ParseError {
+        line: Some(line),
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn parse() {
+        let ini = Ini::parse(
+            "
+# comment
+global = 1
+[rule old]
+if = a = b
+if = c
+[webhook ops]
+",
+        )
+        .unwrap();
+        assert_eq!(
+            ini.entries,
+            [Entry {
+                line: 3,
+                key: "global".to_string(),
+                value: "1".to_string()
+            }]
+        );
+        assert_eq!(ini.sections.len(), 2);
+        assert_eq!(ini.sections[0].entries[0].value, "a = b");
+        assert_eq!(ini.sections[0].value("if"), Some("c"));
+        let rules: Vec<&str> = ini.sections_of("rule").map(|(name, _)| name).collect();
+        assert_eq!(rules, ["old"]);
+        assert_eq!(
+            ini.expect_kinds(&["rule NAME", "webhook NAME"]),
+            Err(error(3, "unknown key: global"))
+        );
+        let e = ini.sections_only("segment").unwrap_err();
+        assert_eq!(e.line(), Some(3));
+        assert_eq!(e.to_string(), "line 3: expected [segment] before settings");
+
+        assert!(Ini::parse("[rule]\nif").is_err());
+        let ini = Ini::parse("[rule]\n[mail x]").unwrap();
+        assert!(ini.expect_kinds(&["rule NAME"]).is_err());
+        let ini = Ini::parse("[rule x]\n[mail x]").unwrap();
+        assert_eq!(
+            ini.expect_kinds(&["rule NAME", "webhook NAME"])
+                .unwrap_err()
+                .to_string(),
+            "line 2: expected [rule NAME] or [webhook NAME]: [mail x]"
+        );
+        let kinds = ["rule NAME", "webhook NAME", "mail"];
+        let ini = Ini::parse(
+            "[mail]
+host = h
+[rule x]",
+        )
+        .unwrap();
+        assert_eq!(ini.expect_kinds(&kinds), Ok(()));
+        assert_eq!(ini.section("mail").unwrap().value("host"), Some("h"));
+        assert_eq!(
+            Ini::parse("[mail x]").unwrap().expect_kinds(&kinds),
+            Err(error(
+                1,
+                "expected [rule NAME], [webhook NAME] or [mail]: [mail x]"
+            ))
+        );
+        assert_eq!(
+            Ini::parse(
+                "[mail]
+[mail]"
+            )
+            .unwrap()
+            .expect_kinds(&kinds),
+            Err(error(2, "[mail] is written twice"))
+        );
+    }
+}
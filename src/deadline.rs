@@ -0,0 +1,258 @@
+use super::article::Article;
+use super::text;
+
+use chrono::{Datelike, Months, NaiveDate};
+use regex::{Captures, Regex};
+use std::ops::Range;
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    // "in July"のように年が書かれていない
+    Low,
+    // "June and July 2023"のように日が書かれていない
+    Medium,
+    // "June 13, 2023"
+    High,
+}
+impl Confidence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deadline {
+    pub start: NaiveDate,
+    // 終日イベントとして扱うので、endは含まない
+    pub end: NaiveDate,
+    pub confidence: Confidence,
+    pub phrase: String,
+}
+
+fn month(name: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    MONTHS.iter().position(|m| *m == name).map(|i| i as u32 + 1)
+}
+
+fn whole_month(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+    Some((start, start.checked_add_months(Months::new(1))?))
+}
+
+#[derive(Debug)]
+pub struct Extractor {
+    month_day_year: Regex,
+    day_month_year: Regex,
+    iso_date: Regex,
+    months_year: Regex,
+    month_name: Regex,
+    month_only: Regex,
+}
+impl Default for Extractor {
+    fn default() -> Self {
+        let month = format!("(?:{})", MONTHS.join("|"));
+        Extractor {
+            month_day_year: Regex::new(&format!(
+                r"(?i)\b({month})\s+(\d{{1,2}})(?:st|nd|rd|th)?,?\s+(\d{{4}})\b"
+            ))
+            .unwrap(),
+            day_month_year: Regex::new(&format!(r"(?i)\b(\d{{1,2}})\s+({month}),?\s+(\d{{4}})\b"))
+                .unwrap(),
+            iso_date: Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})\b").unwrap(),
+            // "June and July 2023", "April, May, or June 2024"
+            months_year: Regex::new(&format!(
+                r"(?i)\b({month}(?:\s*(?:,|,?\s*and|,?\s*or)\s*{month})*)\s+(\d{{4}})\b"
+            ))
+            .unwrap(),
+            month_name: Regex::new(&format!(r"(?i){month}")).unwrap(),
+            month_only: Regex::new(&format!(
+                r"(?i)\b(?:in|by|until|after|starting|beginning|from)\s+({month})\b(\s+\d)?"
+            ))
+            .unwrap(),
+        }
+    }
+}
+impl Extractor {
+    // お知らせの公開日以降の日付だけを返す
+    pub fn extract(&self, a: &Article) -> Vec<Deadline> {
+        let published = a
+            .date
+            .get(..10)
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        let text = format!("{}\n{}", a.title, a.body_text());
+        let mut found: Vec<(Range<usize>, Deadline)> = Vec::new();
+        let mut push = |range: Range<usize>, start, end, confidence, phrase: &str| {
+            if found
+                .iter()
+                .any(|(r, _)| r.start < range.end && range.start < r.end)
+            {
+                return;
+            }
+            found.push((
+                range,
+                Deadline {
+                    start,
+                    end,
+                    confidence,
+                    phrase: text::normalize(phrase),
+                },
+            ));
+        };
+        let single_day = |c: &Captures, y: usize, m: Option<u32>, d: usize| {
+            let m = m.or_else(|| c[2].parse().ok())?;
+            let start = NaiveDate::from_ymd_opt(c[y].parse().ok()?, m, c[d].parse().ok()?)?;
+            Some((start, start.succ_opt()?))
+        };
+        for c in self.month_day_year.captures_iter(&text) {
+            if let Some((start, end)) = single_day(&c, 3, month(&c[1]), 2) {
+                let m = c.get(0).unwrap();
+                push(m.range(), start, end, Confidence::High, m.as_str());
+            }
+        }
+        for c in self.day_month_year.captures_iter(&text) {
+            if let Some((start, end)) = single_day(&c, 3, month(&c[2]), 1) {
+                let m = c.get(0).unwrap();
+                push(m.range(), start, end, Confidence::High, m.as_str());
+            }
+        }
+        for c in self.iso_date.captures_iter(&text) {
+            if let Some((start, end)) = single_day(&c, 1, None, 3) {
+                let m = c.get(0).unwrap();
+                push(m.range(), start, end, Confidence::High, m.as_str());
+            }
+        }
+        for c in self.months_year.captures_iter(&text) {
+            let Ok(year) = c[2].parse() else { continue };
+            let whole = c.get(0).unwrap();
+            for name in self.month_name.find_iter(&c[1]) {
+                let Some((start, end)) = month(name.as_str()).and_then(|m| whole_month(year, m))
+                else {
+                    continue;
+                };
+                // 同じフレーズ内の各月は重複扱いしない
+                let begin = whole.start() + name.start();
+                push(
+                    begin..begin + name.len(),
+                    start,
+                    end,
+                    Confidence::Medium,
+                    &format!("{} {}", name.as_str(), year),
+                );
+            }
+        }
+        if let Some(published) = published {
+            for c in self.month_only.captures_iter(&text) {
+                if c.get(2).is_some() {
+                    continue;
+                }
+                let Some(m) = month(&c[1]) else { continue };
+                // 公開月より前の月は翌年のこととみなす
+                let year = if m < published.month() {
+                    published.year() + 1
+                } else {
+                    published.year()
+                };
+                if let Some((start, end)) = whole_month(year, m) {
+                    let whole = c.get(0).unwrap();
+                    push(whole.range(), start, end, Confidence::Low, whole.as_str());
+                }
+            }
+        }
+        let mut ret: Vec<Deadline> = Vec::new();
+        for (_, d) in found {
+            if published.is_some_and(|p| d.end <= p) {
+                continue;
+            }
+            match ret
+                .iter_mut()
+                .find(|r| r.start == d.start && r.end == d.end)
+            {
+                Some(r) if r.confidence < d.confidence => *r = d,
+                Some(_) => {}
+                None => ret.push(d),
+            }
+        }
+        ret.sort_by(|a, b| a.start.cmp(&b.start).then(b.confidence.cmp(&a.confidence)));
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    fn extract(title: &str, date: &str, body: &str) -> Vec<(String, String, Confidence)> {
+        let a = Article {
+            title: title.to_string(),
+            date: date.to_string(),
+            ..testing::article("1", body)
+        };
+        Extractor::default()
+            .extract(&a)
+            .into_iter()
+            .map(|d| (d.start.to_string(), d.end.to_string(), d.confidence))
+            .collect()
+    }
+    fn expected(v: &[(&str, &str, Confidence)]) -> Vec<(String, String, Confidence)> {
+        v.iter()
+            .map(|(s, e, c)| (s.to_string(), e.to_string(), *c))
+            .collect()
+    }
+    #[test]
+    fn extract_dates() {
+        assert_eq!(
+            extract(
+                "Reminder: End of servicing for Windows 10, version 21H2 on June 13, 2023",
+                "2023-05-24T21:00:00Z",
+                "<div>Released on May 9, 2023. Devices will stop receiving updates on 13 June 2023.</div>"
+            ),
+            expected(&[("2023-06-13", "2023-06-14", Confidence::High)])
+        );
+        assert_eq!(
+            extract(
+                "Reminder: Security hardening changes for Netlogon and Kerberos coming in June and July 2023",
+                "2023-06-01T17:00:00Z",
+                "<div>Enforcement begins 2023-07-11. The final phase is in October.</div>"
+            ),
+            expected(&[
+                ("2023-06-01", "2023-07-01", Confidence::Medium),
+                ("2023-07-01", "2023-08-01", Confidence::Medium),
+                ("2023-07-11", "2023-07-12", Confidence::High),
+                ("2023-10-01", "2023-11-01", Confidence::Low),
+            ])
+        );
+    }
+    #[test]
+    fn infer_year_of_month_only() {
+        assert_eq!(
+            extract(
+                "Heads up: changes coming in February",
+                "2023-11-01T00:00:00Z",
+                "<div>Starting January 2024, and by March 5 as well.</div>"
+            ),
+            expected(&[
+                ("2024-01-01", "2024-02-01", Confidence::Medium),
+                ("2024-02-01", "2024-03-01", Confidence::Low),
+            ])
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use super::article::Article;
+use super::config::{self, Ini};
+use super::state::State;
+use super::summary::Summarizer;
+
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use std::env;
+use std::time::Duration;
+
+// stateに記録する、前回のダイジェストに含めた最後のお知らせのfirst_seen
+pub const WATERMARK: &str = "digest";
+const DEFAULT_SUBJECT: &str = "Windows message center digest";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Security {
+    StartTls,
+    Tls,
+    // ローカルのSMTPサーバー向け
+    None,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MailConfig {
+    host: String,
+    port: Option<u16>,
+    security: Security,
+    username: Option<String>,
+    password: Option<String>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    subject: String,
+}
+impl MailConfig {
+    // 共通の設定ファイルの[mail]のセクションに、1行に1つ key = value を書く
+    pub fn from_config(ini: &Ini) -> Result<Self, String> {
+        let mut host = None;
+        let mut port = None;
+        let mut security = Security::StartTls;
+        let mut username = None;
+        let mut password = None;
+        let mut from = None;
+        let mut to = Vec::new();
+        let mut subject = DEFAULT_SUBJECT.to_string();
+        let section = ini.section("mail").ok_or("[mail] is required")?;
+        for e in &section.entries {
+            let error = |message: &str| config::error(e.line, message).to_string();
+            let value = e.value.as_str();
+            let mailbox = |s: &str| {
+                s.trim()
+                    .parse::<Mailbox>()
+                    .map_err(|e| error(&format!("{}: {}", s.trim(), e)))
+            };
+            match e.key.as_str() {
+                "host" => host = Some(value.to_string()),
+                "port" => port = Some(value.parse().map_err(|_| error("invalid port"))?),
+                "security" => {
+                    security = match value {
+                        "starttls" => Security::StartTls,
+                        "tls" => Security::Tls,
+                        "none" => Security::None,
+                        _ => return Err(error("security must be starttls, tls or none")),
+                    }
+                }
+                "username" => username = Some(value.to_string()),
+                "password" => password = Some(value.to_string()),
+                // パスワードを設定ファイルに書かずに済むように、環境変数から読む
+                "password_env" => {
+                    password =
+                        Some(env::var(value).map_err(|_| error(&format!("{} is not set", value)))?)
+                }
+                "from" => from = Some(mailbox(value)?),
+                "to" => {
+                    for s in value.split(',').filter(|s| !s.trim().is_empty()) {
+                        to.push(mailbox(s)?);
+                    }
+                }
+                "subject" => subject = value.to_string(),
+                k => return Err(error(&format!("unknown key: {}", k))),
+            }
+        }
+        if username.is_some() && password.is_none() {
+            return Err("username needs password or password_env".to_string());
+        }
+        if to.is_empty() {
+            return Err("to is required".to_string());
+        }
+        Ok(MailConfig {
+            host: host.ok_or("host is required")?,
+            port,
+            security,
+            username,
+            password,
+            from: from.ok_or("from is required")?,
+            to,
+            subject,
+        })
+    }
+    fn transport(&self) -> Result<SmtpTransport, String> {
+        let mut builder = match self.security {
+            Security::StartTls => SmtpTransport::starttls_relay(&self.host),
+            Security::Tls => SmtpTransport::relay(&self.host),
+            Security::None => Ok(SmtpTransport::builder_dangerous(&self.host)),
+        }
+        .map_err(|e| e.to_string())?
+        .timeout(Some(Duration::from_secs(60)));
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(builder.build())
+    }
+    pub fn message(&self, articles: &[&Article]) -> Result<Message, String> {
+        let mut builder = Message::builder().from(self.from.clone()).subject(format!(
+            "{} ({} new)",
+            self.subject,
+            articles.len()
+        ));
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        builder
+            .multipart(MultiPart::alternative_plain_html(
+                text(articles),
+                html(articles),
+            ))
+            .map_err(|e| e.to_string())
+    }
+}
+
+pub fn text(articles: &[&Article]) -> String {
+    let summarizer = Summarizer::default();
+    let mut ret = String::new();
+    for a in articles {
+        ret += &format!("{}\n{}\n{}\n", a.title, a.date, a.url);
+        let summary = match a.summary.as_str() {
+            "" => summarizer.summarize(&a.body),
+            s => s.to_string(),
+        };
+        if !summary.is_empty() {
+            ret += &format!("\n{}\n", summary);
+        }
+        ret += "\n";
+    }
+    ret
+}
+
+pub fn html(articles: &[&Article]) -> String {
+    let mut ret =
+        String::from("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"></head>\n<body>\n");
+    for a in articles {
+        ret += &format!(
+            "<h2><a href=\"{}\">{}</a></h2>\n<p>{}</p>\n{}\n<hr>\n",
+            html_escape::encode_double_quoted_attribute(&a.url),
+            html_escape::encode_text(&a.title),
+            html_escape::encode_text(&a.date),
+            a.body
+        );
+    }
+    ret += "</body>\n</html>\n";
+    ret
+}
+
+// 前回のダイジェストの後に初めて見つかったお知らせを、新しい順に返す
+pub fn pending(state: &State) -> Vec<&Article> {
+    let watermark = state.watermarks.get(WATERMARK);
+    let mut ret: Vec<&Article> = state
+        .articles
+        .values()
+        .filter(|a| watermark.is_some_and(|w| a.first_seen > *w))
+        .map(|a| &a.article)
+        .collect();
+    ret.sort_by(|a, b| b.date.cmp(&a.date));
+    ret
+}
+
+fn latest_first_seen(state: &State) -> String {
+    state
+        .articles
+        .values()
+        .map(|a| a.first_seen.as_str())
+        .max()
+        .unwrap_or_default()
+        .to_string()
+}
+
+// ダイジェストを送り、送ったお知らせの数を返す
+// 初回は今あるお知らせを送らずに、ここまで送ったものとして記録する
+pub fn send(config: &MailConfig, state: &mut State) -> Result<usize, String> {
+    if !state.watermarks.contains_key(WATERMARK) {
+        state
+            .watermarks
+            .insert(WATERMARK.to_string(), latest_first_seen(state));
+        return Ok(0);
+    }
+    let articles = pending(state);
+    if articles.is_empty() {
+        return Ok(0);
+    }
+    let message = config.message(&articles)?;
+    config
+        .transport()?
+        .send(&message)
+        .map_err(|e| e.to_string())?;
+    let count = articles.len();
+    state
+        .watermarks
+        .insert(WATERMARK.to_string(), latest_first_seen(state));
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    // 受け取ったメールのDATAを返すだけのSMTPサーバー
+    fn smtp_sink() -> (u16, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                stream.write_all(b"220 localhost ESMTP\r\n").unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 {
+                    let command = line.to_uppercase();
+                    line.clear();
+                    if command.starts_with("DATA") {
+                        stream.write_all(b"354 go ahead\r\n").unwrap();
+                        let mut data = String::new();
+                        while reader.read_line(&mut line).unwrap() > 0 && line != ".\r\n" {
+                            data += &line;
+                            line.clear();
+                        }
+                        line.clear();
+                        tx.send(data).unwrap();
+                        stream.write_all(b"250 queued\r\n").unwrap();
+                    } else if command.starts_with("QUIT") {
+                        stream.write_all(b"221 bye\r\n").unwrap();
+                        break;
+                    } else {
+                        stream.write_all(b"250 localhost\r\n").unwrap();
+                    }
+                }
+            }
+        });
+        (port, rx)
+    }
+    fn article(id: &str, title: &str) -> Article {
+        Article {
+            url: format!("https://support.microsoft.com/help/{}", id),
+            title: title.to_string(),
+            ..testing::article(id, &format!("<div>Body of {}. Second sentence.</div>", id))
+        }
+    }
+    fn parse(s: &str) -> Result<MailConfig, String> {
+        MailConfig::from_config(&Ini::parse(&format!("[mail]\n{}", s))?)
+    }
+    #[test]
+    fn parse_config() {
+        let config = parse(
+            "
+# 社内のSMTPサーバー
+host = smtp.example.com
+port = 587
+username = wmc
+password = secret
+from = Windows message center <wmc@example.com>
+to = a@example.com, B <b@example.com>
+",
+        )
+        .unwrap();
+        assert_eq!(config.security, Security::StartTls);
+        assert_eq!(config.port, Some(587));
+        assert_eq!(config.to.len(), 2);
+        assert_eq!(config.subject, DEFAULT_SUBJECT);
+        assert!(parse("host = h\nfrom = a@example.com").is_err());
+        assert!(MailConfig::from_config(&Ini::parse("[rule x]").unwrap()).is_err());
+        assert!(parse("host = h\nfrom = a@example.com\nto = not an address").is_err());
+        assert!(parse("host = h\nfrom = a@example.com\nto = b@example.com\nusername = u").is_err());
+        assert!(
+            parse("host = h\nfrom = a@example.com\nto = b@example.com\nsecurity = ssl").is_err()
+        );
+        assert!(parse("host = h\nfrom = a@example.com\nto = b@example.com\npassword_env = WMC_DIGEST_TEST_UNSET").is_err());
+    }
+    #[test]
+    fn send_digest() {
+        let (port, rx) = smtp_sink();
+        let config = parse(&format!(
+            "host = 127.0.0.1\nport = {}\nsecurity = none\nfrom = wmc@example.com\nto = ops@example.com\nsubject = Digest",
+            port
+        ))
+        .unwrap();
+        let mut state = State::default();
+        state.merge(&[article("1", "Old")], "2023-06-12T00:00:00Z");
+
+        // 初回は記録だけ
+        assert_eq!(send(&config, &mut state), Ok(0));
+        assert_eq!(state.watermarks[WATERMARK], "2023-06-12T00:00:00Z");
+        assert_eq!(send(&config, &mut state), Ok(0));
+
+        state.merge(
+            &[article("1", "Old"), article("2", "Take action & <more>")],
+            "2023-06-13T00:00:00Z",
+        );
+        assert_eq!(send(&config, &mut state), Ok(1));
+        let data = rx.recv().unwrap();
+        assert!(data.contains("Subject: Digest (1 new)"));
+        assert!(data.contains("To: ops@example.com"));
+        assert!(data.contains("multipart/alternative"));
+        assert!(data.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(data.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(data.contains("\r\nTake action & <more>\r\n"));
+        assert!(data.contains("<div>Body of 2. Second sentence.</div>"));
+        assert!(!data.contains("Old"));
+        assert_eq!(state.watermarks[WATERMARK], "2023-06-13T00:00:00Z");
+
+        assert_eq!(send(&config, &mut state), Ok(0));
+        assert!(rx.try_recv().is_err());
+    }
+    #[test]
+    fn render_text() {
+        let mut a = article("2", "Title");
+        assert_eq!(
+            text(&[&a]),
+            "Title\n2023-06-13T17:00:00Z\nhttps://support.microsoft.com/help/2\n\nBody of 2. Second sentence.\n\n"
+        );
+        a.summary = "Summary.".to_string();
+        assert!(text(&[&a]).contains("\n\nSummary.\n"));
+    }
+}
@@ -1,3 +1,7 @@
+//! Builders of Atom feeds, and of RSS 2.0 channels in [`rss`].
+
+use super::xhtml;
+
 use fmt::Display;
 use std::fmt;
 
@@ -7,15 +11,16 @@ fn to_xml_str<T: Display>(value: &T, var_name: &str) -> String {
 trait IntoXMLString<T = (), U = ()> {
     fn to_xml_str(&self, var_name: &str) -> String;
 }
+// 文字列の要素はテキストとしてエスケープして書く
 impl<T: Display> IntoXMLString<T, T> for T {
     fn to_xml_str(&self, var_name: &str) -> String {
-        to_xml_str(self, var_name)
+        to_xml_str(&html_escape::encode_text(&self.to_string()), var_name)
     }
 }
 impl<T: Display> IntoXMLString<Option<T>, T> for Option<T> {
     fn to_xml_str(&self, var_name: &str) -> String {
         if let Some(v) = self {
-            to_xml_str(&v, var_name)
+            v.to_xml_str(var_name)
         } else {
             String::new()
         }
@@ -41,7 +46,7 @@ impl<T: IntoXMLString> IntoXMLString for Vec<T> {
 }
 macro_rules! concatenated_xml_accessor {
     ($($name:ident),*) => {
-        pub fn as_concatenated_xml(&self) -> String {
+        fn as_concatenated_xml(&self) -> String {
             let mut ret = String::new();
             $(
                 ret += &self.$name.to_xml_str(stringify!($name));
@@ -52,7 +57,7 @@ macro_rules! concatenated_xml_accessor {
 }
 macro_rules! xml_attribute_accessor_impl {
     ($( $name:ident ),*) => {
-        pub fn as_xml_attributes(&self) -> String {
+        fn as_xml_attributes(&self) -> String {
             let mut capacity: usize = 0;
             $(
                 capacity += self.$name.as_deref().map_or(0, |s| s.len());
@@ -63,7 +68,11 @@ macro_rules! xml_attribute_accessor_impl {
                     if !ret.is_empty() {
                         ret += " ";
                     }
-                    ret += &format!(r#"{}="{}""#, stringify!($name).trim_end_matches('_'), v);
+                    ret += &format!(
+                        r#"{}="{}""#,
+                        stringify!($name).trim_end_matches('_'),
+                        html_escape::encode_double_quoted_attribute(v)
+                    );
                 }
             )*
             ret
@@ -73,6 +82,7 @@ macro_rules! xml_attribute_accessor_impl {
 macro_rules! optional_member_setter_impl {
     ($struct_name:ident, $( $name:ident : $into_type:ident),*) => {
         $(
+            #[doc = concat!("Sets `", stringify!($name), "`.")]
             #[allow(dead_code)]
             pub fn $name<S: Into<$into_type>>(self, $name: S) -> Self {
                 $struct_name {
@@ -86,6 +96,7 @@ macro_rules! optional_member_setter_impl {
 macro_rules! vec_member_setter_impl {
     ($struct_name:ident, $( $name:ident: $elem_type:ident ),*) => {
         $(
+            #[doc = concat!("Sets the `", stringify!($name), "` elements.")]
             #[allow(dead_code)]
             pub fn $name(self, $name: Vec<$elem_type>) -> Self {
                 $struct_name {
@@ -96,12 +107,15 @@ macro_rules! vec_member_setter_impl {
         )*
     }
 }
+/// An Atom person construct such as `<author>`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Person {
     name: String,
     uri: Option<String>,
     email: Option<String>,
 }
 impl Person {
+    /// Creates a person with only a name.
     pub fn new<S: Into<String>>(name: S) -> Self {
         Person {
             name: name.into(),
@@ -117,15 +131,19 @@ impl IntoXMLString for Person {
         to_xml_str(&self.as_concatenated_xml(), var_name)
     }
 }
+/// An Atom `<link>`.
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Link {
-    href: Option<HTMLText>,
+    href: Option<String>,
     rel: Option<String>,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
     type_: Option<String>,
     hreflang: Option<String>,
     title: Option<String>,
 }
 impl Link {
+    /// Creates a link without any attribute. Set at least `href`.
     pub fn new() -> Self {
         Link {
             ..Default::default()
@@ -133,7 +151,7 @@ impl Link {
     }
     optional_member_setter_impl!(
         Link,
-        href: HTMLText,
+        href: String,
         rel: String,
         type_: String,
         hreflang: String,
@@ -146,10 +164,77 @@ impl IntoXMLString for Link {
         format!("<{} {} />", var_name, self.as_xml_attributes())
     }
 }
+/// An Atom `<category>`.
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Category {
+    term: Option<String>,
+    scheme: Option<String>,
+    label: Option<String>,
+}
+impl Category {
+    /// Creates a category with only a term.
+    pub fn new<S: Into<String>>(term: S) -> Self {
+        Category {
+            term: Some(term.into()),
+            ..Default::default()
+        }
+    }
+    optional_member_setter_impl!(Category, scheme: String, label: String);
+    xml_attribute_accessor_impl!(term, scheme, label);
+}
+impl IntoXMLString for Category {
+    fn to_xml_str(&self, var_name: &str) -> String {
+        format!("<{} {} />", var_name, self.as_xml_attributes())
+    }
+}
+/// A namespaced extension element. Declare its prefix with [`Namespace`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Extension {
+    name: String,
+    value: String,
+}
+impl Extension {
+    /// Creates `<name>value</name>`. `name` includes the prefix, e.g. `wmc:kb`.
+    pub fn new<S: Into<String>, T: AsRef<str>>(name: S, value: T) -> Self {
+        Extension {
+            name: name.into(),
+            value: value.as_ref().to_string(),
+        }
+    }
+}
+impl IntoXMLString for Extension {
+    fn to_xml_str(&self, _var_name: &str) -> String {
+        self.value.to_xml_str(&self.name)
+    }
+}
+/// An XML namespace declared on `<feed>`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Namespace {
+    prefix: String,
+    uri: String,
+}
+impl Namespace {
+    /// Declares `xmlns:prefix="uri"`.
+    pub fn new<S: Into<String>, T: Into<String>>(prefix: S, uri: T) -> Self {
+        Namespace {
+            prefix: prefix.into(),
+            uri: uri.into(),
+        }
+    }
+}
+/// Text of `type="html"`. The string is escaped when written.
+///
+/// With the `serde` feature it is serialized as the string before escaping, and escaped
+/// again when deserialized.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
 pub struct HTMLText {
     text: String,
 }
 impl HTMLText {
+    /// Creates the text from an unescaped string, e.g. HTML markup to show as is.
     pub fn new(s: &str) -> Self {
         HTMLText {
             text: String::from(html_escape::encode_text(s)),
@@ -166,6 +251,11 @@ impl From<&str> for HTMLText {
         Self::new(s)
     }
 }
+impl From<HTMLText> for String {
+    fn from(t: HTMLText) -> Self {
+        html_escape::decode_html_entities(&t.text).into_owned()
+    }
+}
 impl IntoXMLString for HTMLText {
     fn to_xml_str(&self, var_name: &str) -> String {
         format!(r#"<{} type="html">{}</{}>"#, var_name, self.text, var_name)
@@ -177,17 +267,94 @@ impl std::ops::Deref for HTMLText {
         &self.text
     }
 }
+/// Text of `type="xhtml"`. The HTML is converted to well-formed XHTML when created.
+///
+/// With the `serde` feature it is serialized as the XHTML, and converted again when
+/// deserialized.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
+pub struct XHTMLText {
+    text: String,
+}
+impl XHTMLText {
+    /// Creates the text from HTML, converting it to XHTML.
+    pub fn new(html: &str) -> Self {
+        XHTMLText {
+            text: xhtml::from_html(html),
+        }
+    }
+    // xhtml::from_htmlで変換済みの断片をそのまま埋め込む
+    pub(crate) fn from_xhtml(text: String) -> Self {
+        XHTMLText { text }
+    }
+}
+impl From<String> for XHTMLText {
+    fn from(s: String) -> Self {
+        Self::new(&s)
+    }
+}
+impl From<XHTMLText> for String {
+    fn from(t: XHTMLText) -> Self {
+        t.text
+    }
+}
+impl IntoXMLString for XHTMLText {
+    fn to_xml_str(&self, var_name: &str) -> String {
+        format!(
+            r#"<{} type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml">{}</div></{}>"#,
+            var_name, self.text, var_name
+        )
+    }
+}
+/// The `<content>` of an entry.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Content {
+    /// `type="html"`.
+    Html(HTMLText),
+    /// `type="xhtml"`.
+    Xhtml(XHTMLText),
+}
+impl From<String> for Content {
+    fn from(s: String) -> Self {
+        Content::Html(s.into())
+    }
+}
+impl From<HTMLText> for Content {
+    fn from(t: HTMLText) -> Self {
+        Content::Html(t)
+    }
+}
+impl From<XHTMLText> for Content {
+    fn from(t: XHTMLText) -> Self {
+        Content::Xhtml(t)
+    }
+}
+impl IntoXMLString for Content {
+    fn to_xml_str(&self, var_name: &str) -> String {
+        match self {
+            Content::Html(t) => t.to_xml_str(var_name),
+            Content::Xhtml(t) => t.to_xml_str(var_name),
+        }
+    }
+}
 
+/// An Atom `<entry>`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entry {
     id: String,
     title: HTMLText,
     updated: String,
     author: Vec<Person>,
-    content: Option<HTMLText>,
-    link: Option<Link>,
+    content: Option<Content>,
+    link: Vec<Link>,
     summary: Option<HTMLText>,
+    category: Vec<Category>,
+    extension: Vec<Extension>,
 }
 impl Entry {
+    /// Creates an entry with the required `<id>`, `<title>` and `<updated>` (RFC 3339).
     pub fn new(id: String, title: HTMLText, updated: String) -> Self {
         Entry {
             id,
@@ -195,19 +362,52 @@ impl Entry {
             updated,
             author: Default::default(),
             content: None,
-            link: None,
+            link: Default::default(),
             summary: None,
+            category: Default::default(),
+            extension: Default::default(),
         }
     }
-    optional_member_setter_impl!(Entry, content: HTMLText, link: Link, summary: HTMLText);
-    vec_member_setter_impl!(Entry, author: Person);
-    concatenated_xml_accessor!(id, title, updated, author, content, link, summary);
+    optional_member_setter_impl!(Entry, content: Content, summary: HTMLText);
+    vec_member_setter_impl!(
+        Entry,
+        author: Person,
+        link: Link,
+        category: Category,
+        extension: Extension
+    );
+    concatenated_xml_accessor!(
+        id, title, updated, author, content, link, summary, category, extension
+    );
 }
 impl IntoXMLString for Entry {
     fn to_xml_str(&self, var_name: &str) -> String {
         to_xml_str(&self.as_concatenated_xml(), var_name)
     }
 }
+/// An Atom feed, built with the setters and written with [`Feed::to_xml`].
+///
+/// ```
+/// use unofficial_windows_message_center_rss::feed::{Entry, Feed, Link};
+///
+/// let entry = Entry::new(
+///     "urn:example:1".to_string(),
+///     "Hello & welcome".into(),
+///     "2023-06-13T17:00:00Z".to_string(),
+/// )
+/// .content("<p>Body</p>".to_string());
+/// let feed = Feed::new(
+///     "urn:example".to_string(),
+///     "Example".to_string(),
+///     "2023-06-13T17:00:00Z".to_string(),
+/// )
+/// .link(vec![Link::new().href("https://example.com/atom.xml").rel("self")])
+/// .entry(vec![entry]);
+/// let xml = feed.to_xml();
+/// assert!(xml.contains("<title>Example</title>"));
+/// assert!(xml.contains(r#"<title type="html">Hello &amp; welcome</title>"#));
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Feed {
     id: String,
     title: String,
@@ -215,8 +415,10 @@ pub struct Feed {
     author: Vec<Person>,
     link: Vec<Link>,
     entry: Vec<Entry>,
+    namespace: Vec<Namespace>,
 }
 impl Feed {
+    /// Creates a feed with the required `<id>`, `<title>` and `<updated>` (RFC 3339).
     pub fn new(id: String, title: String, updated: String) -> Self {
         Feed {
             id,
@@ -225,14 +427,59 @@ impl Feed {
             author: Default::default(),
             link: Default::default(),
             entry: Default::default(),
+            namespace: Default::default(),
         }
     }
-    vec_member_setter_impl!(Feed, author: Person, link: Link, entry: Entry);
+    vec_member_setter_impl!(
+        Feed,
+        author: Person,
+        link: Link,
+        entry: Entry,
+        namespace: Namespace
+    );
     concatenated_xml_accessor!(id, title, updated, author, link, entry);
+    /// Writes the feed as an XML document.
     pub fn to_xml(&self) -> String {
+        let mut namespaces = String::new();
+        for ns in &self.namespace {
+            namespaces += &format!(
+                r#" xmlns:{}="{}""#,
+                ns.prefix,
+                html_escape::encode_double_quoted_attribute(&ns.uri)
+            );
+        }
         format!(
-            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">{}</feed>",
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\"{}>{}</feed>",
+            namespaces,
             self.as_concatenated_xml()
         )
     }
 }
+
+pub mod rss;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn escape_attributes() {
+        assert_eq!(
+            Category::new("a&b").label("\"x\"").to_xml_str("category"),
+            r#"<category term="a&amp;b" label="&quot;x&quot;" />"#
+        );
+        assert_eq!(
+            Link::new()
+                .href("https://example.com/?a=1&b=<2>")
+                .title("\"KB\" & more")
+                .to_xml_str("link"),
+            r#"<link href="https://example.com/?a=1&amp;b=&lt;2&gt;" title="&quot;KB&quot; &amp; more" />"#
+        );
+    }
+    #[test]
+    fn xhtml_text() {
+        assert_eq!(
+            XHTMLText::new("<p>a<br>b & c</content><script>x").to_xml_str("content"),
+            r#"<content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p>a<br/>b &amp; c<script>x</script></p></div></content>"#
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Builders of RSS 2.0 channels.
+
+use super::*;
+
+fn escaped(s: &str) -> String {
+    String::from(html_escape::encode_text(s))
+}
+
+/// An RSS `<item>`. The strings are escaped when written.
+pub struct Item {
+    title: String,
+    link: Option<String>,
+    description: Option<String>,
+    guid: String,
+    pub_date: String,
+    category: Vec<(String, String)>,
+}
+impl Item {
+    /// Creates an item with the required `<guid>`, `<title>` and `<pubDate>` (RFC 2822).
+    pub fn new(guid: String, title: String, pub_date: String) -> Self {
+        Item {
+            title,
+            link: None,
+            description: None,
+            guid,
+            pub_date,
+            category: Vec::new(),
+        }
+    }
+    optional_member_setter_impl!(Item, link: String, description: String);
+    /// Sets the `<category>` elements as pairs of the domain and the name.
+    pub fn category(self, category: Vec<(String, String)>) -> Self {
+        Item { category, ..self }
+    }
+}
+impl IntoXMLString for Item {
+    fn to_xml_str(&self, var_name: &str) -> String {
+        let mut ret = to_xml_str(&escaped(&self.title), "title");
+        if let Some(link) = &self.link {
+            ret += &to_xml_str(&escaped(link), "link");
+        }
+        if let Some(description) = &self.description {
+            ret += &to_xml_str(&escaped(description), "description");
+        }
+        ret += &format!(
+            r#"<guid isPermaLink="false">{}</guid>"#,
+            escaped(&self.guid)
+        );
+        ret += &to_xml_str(&self.pub_date, "pubDate");
+        for (domain, name) in &self.category {
+            ret += &format!(
+                r#"<category domain="{}">{}</category>"#,
+                html_escape::encode_double_quoted_attribute(domain),
+                escaped(name)
+            );
+        }
+        to_xml_str(&ret, var_name)
+    }
+}
+
+/// An RSS `<channel>`, written with [`Channel::to_xml`].
+pub struct Channel {
+    title: String,
+    link: String,
+    description: String,
+    self_link: String,
+    last_build_date: String,
+    item: Vec<Item>,
+}
+impl Channel {
+    /// Creates a channel. `self_link` is written as `<atom:link rel="self">`.
+    pub fn new(
+        title: String,
+        link: String,
+        description: String,
+        self_link: String,
+        last_build_date: String,
+    ) -> Self {
+        Channel {
+            title,
+            link,
+            description,
+            self_link,
+            last_build_date,
+            item: Default::default(),
+        }
+    }
+    vec_member_setter_impl!(Channel, item: Item);
+    /// Writes the channel as an XML document.
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\"><channel>{}{}{}<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\" />{}{}</channel></rss>",
+            to_xml_str(&escaped(&self.title), "title"),
+            to_xml_str(&escaped(&self.link), "link"),
+            to_xml_str(&escaped(&self.description), "description"),
+            html_escape::encode_double_quoted_attribute(&self.self_link),
+            to_xml_str(&self.last_build_date, "lastBuildDate"),
+            self.item.to_xml_str("item"),
+        )
+    }
+}
@@ -0,0 +1,239 @@
+use super::article::{self, Article, Category};
+use super::render::MSRC_UPDATE_GUIDE_URL;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+#[derive(Default)]
+struct EntryFields {
+    id: String,
+    title: String,
+    updated: String,
+    content: String,
+    summary: String,
+    href: Option<String>,
+    rel: Option<String>,
+    categories: Vec<Category>,
+    kbs: Vec<String>,
+    builds: Vec<String>,
+    versions: Vec<String>,
+    cves: Vec<String>,
+}
+impl EntryFields {
+    fn into_article(self) -> Option<Article> {
+        let id = self.id.rsplit('#').next()?.to_string();
+        if id.is_empty() {
+            return None;
+        }
+        let mut ret = Article::new(
+            id,
+            self.href.unwrap_or_default(),
+            article::static_rel(self.rel.as_deref().unwrap_or("alternate")),
+            self.title,
+            self.updated,
+            self.content,
+        );
+        ret.summary = html_escape::decode_html_entities(&self.summary).into_owned();
+        ret.categories = self.categories;
+        ret.kbs = self.kbs;
+        ret.builds = self.builds;
+        ret.versions = self.versions;
+        ret.cves = self.cves;
+        Some(ret)
+    }
+}
+
+fn read_link(e: &BytesStart, fields: &mut EntryFields) -> quick_xml::Result<()> {
+    let mut href = None;
+    let mut rel = None;
+    for attr in e.attributes() {
+        let attr = attr?;
+        let value = attr.unescape_value()?.into_owned();
+        match attr.key.local_name().as_ref() {
+            b"href" => href = Some(value),
+            b"rel" => rel = Some(value),
+            _ => {}
+        }
+    }
+    let cve = href
+        .as_deref()
+        .and_then(|h| h.strip_prefix(MSRC_UPDATE_GUIDE_URL));
+    match (rel.as_deref(), cve) {
+        (Some("related"), Some(cve)) => fields.cves.push(cve.to_string()),
+        // 最初のlinkがお知らせ本体へのリンク
+        _ if fields.href.is_none() => {
+            fields.href = href;
+            fields.rel = rel;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn read_category(e: &BytesStart, fields: &mut EntryFields) -> quick_xml::Result<()> {
+    let mut category = Category {
+        term: String::new(),
+        scheme: String::new(),
+        label: String::new(),
+    };
+    for attr in e.attributes() {
+        let attr = attr?;
+        let value = attr.unescape_value()?.into_owned();
+        match attr.key.local_name().as_ref() {
+            b"term" => category.term = value,
+            b"scheme" => category.scheme = value,
+            b"label" => category.label = value,
+            _ => {}
+        }
+    }
+    fields.categories.push(category);
+    Ok(())
+}
+
+fn is_xhtml(e: &BytesStart) -> quick_xml::Result<bool> {
+    for attr in e.attributes() {
+        let attr = attr?;
+        if attr.key.local_name().as_ref() == b"type" {
+            return Ok(attr.value.as_ref() == b"xhtml");
+        }
+    }
+    Ok(false)
+}
+
+// type="xhtml"の<content>は、外側のdivの中身を本文とする
+fn unwrap_xhtml_div(inner: &str) -> String {
+    let inner = inner.trim();
+    inner
+        .strip_prefix("<div")
+        .and_then(|s| s.split_once('>'))
+        .and_then(|(_, s)| s.strip_suffix("</div>"))
+        .unwrap_or(inner)
+        .to_string()
+}
+
+// このツール自身が出力したAtomフィードをArticleの列に戻す
+pub fn parse_atom(xml: &str) -> quick_xml::Result<Vec<Article>> {
+    let mut reader = Reader::from_str(xml);
+    let mut ret = Vec::new();
+    let mut entry: Option<EntryFields> = None;
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut raw = String::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = e.local_name().as_ref().to_vec();
+                if let Some(fields) = entry.as_mut() {
+                    if name == b"content" && is_xhtml(&e)? {
+                        fields.content = unwrap_xhtml_div(&reader.read_text(e.name())?);
+                        continue;
+                    }
+                }
+                if name == b"entry" {
+                    entry = Some(Default::default());
+                }
+                path.push(name);
+                raw.clear();
+            }
+            Event::Empty(e) => match (entry.as_mut(), e.local_name().as_ref()) {
+                (Some(fields), b"link") => read_link(&e, fields)?,
+                (Some(fields), b"category") => read_category(&e, fields)?,
+                _ => {}
+            },
+            Event::Text(e) => raw += &e.decode()?,
+            Event::CData(e) => raw += &quick_xml::escape::escape(e.decode()?),
+            Event::GeneralRef(e) => {
+                raw += "&";
+                raw += &e.decode()?;
+                raw += ";";
+            }
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                let in_entry = path.last().is_some_and(|p| p == b"entry");
+                if let (true, Some(fields)) = (in_entry, entry.as_mut()) {
+                    let text = quick_xml::escape::unescape(&raw)?.into_owned();
+                    match name.as_slice() {
+                        b"id" => fields.id = text,
+                        b"title" => fields.title = text,
+                        b"updated" => fields.updated = text,
+                        b"content" => fields.content = text,
+                        b"summary" => fields.summary = text,
+                        b"kb" => fields.kbs.push(text),
+                        b"build" => fields.builds.push(text),
+                        b"version" => fields.versions.push(text),
+                        _ => {}
+                    }
+                } else if name == b"entry" {
+                    ret.extend(entry.take().and_then(EntryFields::into_article));
+                }
+                raw.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render;
+    #[test]
+    fn roundtrip_rendered_feed() {
+        let mut articles = vec![
+            Article::new(
+                "3110".to_string(),
+                "https://support.microsoft.com/help/5027231".to_string(),
+                "alternate",
+                "Take action: June 2023 security update is now available".to_string(),
+                "2023-06-13T17:00:00Z".to_string(),
+                "<div>Security &amp; quality <a href=\"https://aka.ms/x\">update</a></div>"
+                    .to_string(),
+            ),
+            Article::new(
+                "3113".to_string(),
+                "https://example.com/atom.xml".to_string(),
+                "self",
+                "Reminder: Netlogon <and> Kerberos".to_string(),
+                "2023-06-12T17:00:00Z".to_string(),
+                "<div>&nbsp;</div>".to_string(),
+            ),
+        ];
+        articles[0].categories = vec![Category {
+            term: "take-action".to_string(),
+            scheme: "https://example.com/type".to_string(),
+            label: "Take action".to_string(),
+        }];
+        articles[0].kbs = vec!["5027231".to_string()];
+        articles[0].builds = vec!["22621.1848".to_string()];
+        articles[0].summary = "Security & quality update <1>".to_string();
+        articles[1].versions = vec!["21H2".to_string(), "22H2".to_string()];
+        articles[1].cves = vec!["CVE-2022-37966".to_string(), "CVE-2022-37967".to_string()];
+        let xml = render::atom(
+            "https://example.com/atom.xml",
+            articles.clone(),
+            "2023-06-14T00:00:00Z".to_string(),
+            render::ContentType::Html,
+        )
+        .to_xml();
+        assert_eq!(parse_atom(&xml).unwrap(), articles);
+        let xml = render::atom(
+            "https://example.com/atom.xml",
+            articles,
+            "2023-06-14T00:00:00Z".to_string(),
+            render::ContentType::Xhtml,
+        )
+        .to_xml();
+        let parsed = parse_atom(&xml).unwrap();
+        assert_eq!(
+            parsed[0].body,
+            r#"<div>Security &amp; quality <a href="https://aka.ms/x">update</a></div>"#
+        );
+        assert_eq!(parsed[1].body, "<div>&#160;</div>");
+        assert_eq!(parsed[1].title, "Reminder: Netlogon <and> Kerberos");
+    }
+    #[test]
+    fn parse_invalid() {
+        assert!(parse_atom("<feed><entry><id>1</title></entry></feed>").is_err());
+    }
+}
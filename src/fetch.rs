@@ -0,0 +1,262 @@
+use super::hash::fnv1a64;
+use super::warc::WarcWriter;
+
+use chrono::Utc;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const USER_AGENT: &str =
+    "Mozilla/5.0 reqwest/0.12.23 https://github.com/yumetodo/unofficial-windows-message-center-rss";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    pub timestamp: String,
+}
+
+/// An error while reading the Windows message center.
+#[derive(Debug)]
+pub enum FetchError {
+    Http(reqwest::Error),
+    Io(io::Error),
+    Malformed(PathBuf),
+    NotRecorded { url: String, path: PathBuf },
+    // 2xx以外の応答。記録から読んだときも同じ
+    Status { url: String, status: u16 },
+}
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Http(e) => write!(f, "http error: {}", e),
+            FetchError::Io(e) => write!(f, "io error: {}", e),
+            FetchError::Malformed(path) => write!(f, "malformed recording: {}", path.display()),
+            FetchError::NotRecorded { url, path } => {
+                write!(f, "no recording for {} (expected {})", url, path.display())
+            }
+            FetchError::Status { url, status } => write!(f, "HTTP {} from {}", status, url),
+        }
+    }
+}
+impl std::error::Error for FetchError {}
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        FetchError::Http(e)
+    }
+}
+impl From<io::Error> for FetchError {
+    fn from(e: io::Error) -> Self {
+        FetchError::Io(e)
+    }
+}
+
+/// Where [`Fetcher`] reads the page from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchMode {
+    Web,
+    /// Fetches from the web and saves the response into the directory.
+    Record(PathBuf),
+    /// Reads a response saved by `Record` instead of the web.
+    Replay(PathBuf),
+}
+
+/// Fetches pages, optionally recording them.
+pub struct Fetcher {
+    mode: FetchMode,
+    client: Option<reqwest::blocking::Client>,
+    warc: Option<WarcWriter>,
+}
+impl Fetcher {
+    pub fn new(mode: FetchMode) -> Result<Self, FetchError> {
+        let client = match mode {
+            FetchMode::Replay(_) => None,
+            _ => Some(
+                reqwest::blocking::Client::builder()
+                    .user_agent(USER_AGENT)
+                    .build()?,
+            ),
+        };
+        if let FetchMode::Record(dir) = &mode {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(Fetcher {
+            mode,
+            client,
+            warc: None,
+        })
+    }
+    /// Also appends every response to a WARC file.
+    pub fn warc(self, warc: WarcWriter) -> Self {
+        Fetcher {
+            warc: Some(warc),
+            ..self
+        }
+    }
+    pub fn fetch(&self, url: &str) -> Result<Response, FetchError> {
+        let response = self.fetch_by_mode(url)?;
+        if let Some(warc) = &self.warc {
+            warc.write(&response, USER_AGENT)?;
+        }
+        // エラーのページも記録やWARCには残すが、お知らせとしては読まない
+        if !(200..300).contains(&response.status) {
+            return Err(FetchError::Status {
+                url: response.url,
+                status: response.status,
+            });
+        }
+        Ok(response)
+    }
+    fn fetch_by_mode(&self, url: &str) -> Result<Response, FetchError> {
+        match &self.mode {
+            FetchMode::Web => self.fetch_from_web(url),
+            FetchMode::Record(dir) => {
+                let response = self.fetch_from_web(url)?;
+                fs::write(recording_path(dir, url), to_recording(&response))?;
+                Ok(response)
+            }
+            FetchMode::Replay(dir) => {
+                let path = recording_path(dir, url);
+                match fs::read_to_string(&path) {
+                    Ok(s) => from_recording(&s).ok_or(FetchError::Malformed(path)),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Err(FetchError::NotRecorded {
+                        url: url.to_string(),
+                        path,
+                    }),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+    fn fetch_from_web(&self, url: &str) -> Result<Response, FetchError> {
+        let client = self
+            .client
+            .as_ref()
+            .expect("client is created unless replaying");
+        let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        let res = client.get(url).send()?;
+        let status = res.status().as_u16();
+        let headers = res
+            .headers()
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.to_string(),
+                    String::from_utf8_lossy(v.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        let body = res.text()?;
+        Ok(Response {
+            url: url.to_string(),
+            status,
+            headers,
+            body,
+            timestamp,
+        })
+    }
+}
+
+pub fn recording_path(dir: &Path, url: &str) -> PathBuf {
+    dir.join(format!("{:016x}.http", fnv1a64(url.as_bytes())))
+}
+
+// 人が読んで差分を取れるように、メタデータとヘッダを1行ずつ書いた後に空行を挟んでbodyを書く
+fn to_recording(response: &Response) -> String {
+    let mut ret = format!(
+        "URL: {}\nStatus: {}\nTimestamp: {}\n",
+        response.url, response.status, response.timestamp
+    );
+    for (name, value) in &response.headers {
+        ret += &format!("Header: {}: {}\n", name, value);
+    }
+    ret + "\n" + &response.body
+}
+
+fn from_recording(s: &str) -> Option<Response> {
+    let (head, body) = s.split_once("\n\n")?;
+    let mut url = None;
+    let mut status = None;
+    let mut timestamp = None;
+    let mut headers = Vec::new();
+    for line in head.lines() {
+        let (key, value) = line.split_once(": ")?;
+        match key {
+            "URL" => url = Some(value.to_string()),
+            "Status" => status = value.parse().ok(),
+            "Timestamp" => timestamp = Some(value.to_string()),
+            "Header" => {
+                let (name, value) = value.split_once(": ")?;
+                headers.push((name.to_string(), value.to_string()));
+            }
+            _ => return None,
+        }
+    }
+    Some(Response {
+        url: url?,
+        status: status?,
+        headers,
+        body: body.to_string(),
+        timestamp: timestamp?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn response() -> Response {
+        Response {
+            url: "https://learn.microsoft.com/en-us/windows/release-health/windows-message-center"
+                .to_string(),
+            status: 200,
+            headers: vec![
+                (
+                    "content-type".to_string(),
+                    "text/html; charset=utf-8".to_string(),
+                ),
+                ("etag".to_string(), "\"0x8DB6\"".to_string()),
+            ],
+            body: "<!DOCTYPE html>\n<html>\n\n<body></body>\n</html>".to_string(),
+            timestamp: "2023-06-14T00:00:00Z".to_string(),
+        }
+    }
+    #[test]
+    fn recording_roundtrip() {
+        let r = response();
+        assert_eq!(from_recording(&to_recording(&r)), Some(r));
+    }
+    #[test]
+    fn replay_recorded_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let r = response();
+        fs::write(recording_path(dir.path(), &r.url), to_recording(&r)).unwrap();
+        let fetcher = Fetcher::new(FetchMode::Replay(dir.path().to_path_buf())).unwrap();
+        assert_eq!(fetcher.fetch(&r.url).unwrap(), r);
+    }
+    #[test]
+    fn replay_error_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let r = Response {
+            status: 503,
+            ..response()
+        };
+        fs::write(recording_path(dir.path(), &r.url), to_recording(&r)).unwrap();
+        let fetcher = Fetcher::new(FetchMode::Replay(dir.path().to_path_buf())).unwrap();
+        let err = fetcher.fetch(&r.url).unwrap_err();
+        assert!(matches!(err, FetchError::Status { status: 503, .. }));
+        assert_eq!(err.to_string(), format!("HTTP 503 from {}", r.url));
+    }
+    #[test]
+    fn replay_without_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let fetcher = Fetcher::new(FetchMode::Replay(dir.path().to_path_buf())).unwrap();
+        let err = fetcher.fetch("https://example.com/").unwrap_err();
+        assert!(matches!(err, FetchError::NotRecorded { .. }));
+        assert!(err
+            .to_string()
+            .starts_with("no recording for https://example.com/"));
+    }
+}
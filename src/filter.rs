@@ -0,0 +1,211 @@
+use super::article::Article;
+use super::text;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Filter {
+    q: Vec<String>,
+    category: Vec<String>,
+    cve: Vec<String>,
+    exclude: Vec<String>,
+    since: Option<String>,
+    limit: Option<usize>,
+}
+
+pub fn parse_since(s: &str) -> Option<String> {
+    let dt = match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(d) => d.and_hms_opt(0, 0, 0)?.and_utc(),
+        Err(_) => DateTime::parse_from_rfc3339(s).ok()?.with_timezone(&Utc),
+    };
+    Some(dt.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+}
+
+impl Filter {
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let mut ret = Filter::default();
+        for (key, value) in reqwest::Url::parse(&format!("http://localhost/?{}", query))
+            .map_err(|e| e.to_string())?
+            .query_pairs()
+        {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            match key.as_ref() {
+                "q" => ret.q.push(text::normalize(value).to_lowercase()),
+                "category" => ret.category.extend(
+                    value
+                        .split(',')
+                        .map(|s| s.trim().to_lowercase())
+                        .filter(|s| !s.is_empty()),
+                ),
+                "cve" => ret.cve.extend(
+                    value
+                        .split(',')
+                        .map(|s| s.trim().to_uppercase())
+                        .filter(|s| !s.is_empty()),
+                ),
+                "exclude" => ret.exclude.push(text::normalize(value).to_lowercase()),
+                "since" => {
+                    ret.since = Some(parse_since(value).ok_or_else(|| {
+                        format!("since must be YYYY-MM-DD or RFC 3339: {}", value)
+                    })?)
+                }
+                "limit" => {
+                    ret.limit = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("limit must be a number: {}", value))?,
+                    )
+                }
+                _ => return Err(format!("unknown query parameter: {}", key)),
+            }
+        }
+        ret.q.sort();
+        ret.q.dedup();
+        ret.category.sort();
+        ret.category.dedup();
+        ret.cve.sort();
+        ret.cve.dedup();
+        ret.exclude.sort();
+        ret.exclude.dedup();
+        Ok(ret)
+    }
+    // 同じ条件なら同じフィードになるように、パラメータの順序や重複を正規化したクエリを返す
+    pub fn canonical_query(&self) -> String {
+        let mut url = reqwest::Url::parse("http://localhost/").unwrap();
+        {
+            let mut pairs = url.query_pairs_mut();
+            for q in &self.q {
+                pairs.append_pair("q", q);
+            }
+            if !self.category.is_empty() {
+                pairs.append_pair("category", &self.category.join(","));
+            }
+            if !self.cve.is_empty() {
+                pairs.append_pair("cve", &self.cve.join(","));
+            }
+            for e in &self.exclude {
+                pairs.append_pair("exclude", e);
+            }
+            if let Some(since) = &self.since {
+                pairs.append_pair("since", since);
+            }
+            if let Some(limit) = self.limit {
+                pairs.append_pair("limit", &limit.to_string());
+            }
+        }
+        url.query().unwrap_or_default().to_string()
+    }
+    fn matches(&self, a: &Article) -> bool {
+        if self.since.as_ref().is_some_and(|since| a.date < *since) {
+            return false;
+        }
+        if !self.category.is_empty()
+            && !a.categories.iter().any(|c| self.category.contains(&c.term))
+        {
+            return false;
+        }
+        if !self.cve.is_empty() && !a.cves.iter().any(|c| self.cve.contains(c)) {
+            return false;
+        }
+        if self.q.is_empty() && self.exclude.is_empty() {
+            return true;
+        }
+        let text = format!("{}\n{}", a.title, a.body_text()).to_lowercase();
+        self.q.iter().all(|q| text.contains(q.as_str()))
+            && !self.exclude.iter().any(|e| text.contains(e.as_str()))
+    }
+    pub fn apply(&self, articles: Vec<Article>) -> Vec<Article> {
+        articles
+            .into_iter()
+            .filter(|a| self.matches(a))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::classify::Classifier;
+    use crate::metadata::Extractor;
+    use crate::testing;
+    fn article(id: &str, title: &str, date: &str, body: &str) -> Article {
+        let mut a = Article {
+            url: String::new(),
+            title: title.to_string(),
+            date: date.to_string(),
+            ..testing::article(id, body)
+        };
+        a.categories = Classifier::default().classify(&a);
+        Extractor::default().extract(&mut a);
+        a
+    }
+    fn articles() -> Vec<Article> {
+        vec![
+            article(
+                "1",
+                "Take action: June 2023 security update is now available",
+                "2023-06-13T17:00:00Z",
+                "<div>for Windows 11 and all supported versions of <b>Windows 10</b></div>",
+            ),
+            article(
+                "2",
+                "Reminder: Security hardening changes for Netlogon and Kerberos",
+                "2023-06-01T17:00:00Z",
+                "<div>Windows Server, CVE-2022-37967 and CVE-2022-37966</div>",
+            ),
+            article(
+                "3",
+                "Reminder: End of servicing for Windows 10, version 21H2",
+                "2023-05-24T21:00:00Z",
+                "<div>Windows 10 Home and Pro</div>",
+            ),
+        ]
+    }
+    fn ids(filter: &str) -> Vec<String> {
+        Filter::from_query(filter)
+            .unwrap()
+            .apply(articles())
+            .into_iter()
+            .map(|a| a.id)
+            .collect()
+    }
+    #[test]
+    fn apply() {
+        assert_eq!(ids(""), ["1", "2", "3"]);
+        assert_eq!(ids("q=windows+10"), ["1", "3"]);
+        assert_eq!(ids("q=windows+11&q=KERBEROS"), Vec::<String>::new());
+        assert_eq!(ids("q=kerberos"), ["2"]);
+        assert_eq!(ids("category=reminder"), ["2", "3"]);
+        assert_eq!(ids("category=take-action,unknown"), ["1"]);
+        assert_eq!(ids("category=windows-server,hardening"), ["2"]);
+        assert_eq!(ids("exclude=windows+server"), ["1", "3"]);
+        assert_eq!(ids("since=2023-06-01"), ["1", "2"]);
+        assert_eq!(ids("since=2023-06-02T02:00:00%2B09:00"), ["1", "2"]);
+        assert_eq!(ids("category=reminder&limit=1"), ["2"]);
+        assert_eq!(ids("cve=cve-2022-37966"), ["2"]);
+        assert_eq!(ids("cve=CVE-2022-00001,CVE-2022-37967"), ["2"]);
+        assert_eq!(ids("cve=CVE-2022-00001"), Vec::<String>::new());
+    }
+    #[test]
+    fn canonical_query() {
+        let a = Filter::from_query("limit=5&q=Kerberos&category=reminder,take-action").unwrap();
+        let b = Filter::from_query("category=take-action&q=kerberos&category=reminder&limit=5")
+            .unwrap();
+        assert_eq!(a, b);
+        assert_eq!(
+            a.canonical_query(),
+            "q=kerberos&category=reminder%2Ctake-action&limit=5"
+        );
+        assert_eq!(Filter::from_query("q=&limit=").unwrap(), Filter::default());
+    }
+    #[test]
+    fn invalid_query() {
+        assert!(Filter::from_query("limit=ten").is_err());
+        assert!(Filter::from_query("since=yesterday").is_err());
+        assert!(Filter::from_query("sort=date").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Marking the announcements relevant to the Windows machines in use.
+
+use super::article::{Article, Category};
+use super::classify::PRODUCT_SCHEME;
+use super::config::{self, Ini, ParseError};
+use super::text;
+
+use regex::Regex;
+
+/// The scheme of the category with the [`Relevance`] of an announcement.
+pub const RELEVANCE_SCHEME: &str =
+    "https://github.com/yumetodo/unofficial-windows-message-center-rss/categories/relevance";
+/// The scheme of the categories with the names of the affected segments.
+pub const SEGMENT_SCHEME: &str =
+    "https://github.com/yumetodo/unofficial-windows-message-center-rss/categories/fleet";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// How an announcement relates to the fleet.
+pub enum Relevance {
+    /// It is about products or versions no segment uses.
+    Irrelevant,
+    /// It does not name a product or a version.
+    PossiblyRelevant,
+    /// It affects at least one segment.
+    Relevant,
+}
+impl Relevance {
+    /// The term of the category.
+    pub fn term(&self) -> &'static str {
+        match self {
+            Relevance::Irrelevant => "irrelevant",
+            Relevance::PossiblyRelevant => "possibly-relevant",
+            Relevance::Relevant => "relevant",
+        }
+    }
+    fn label(&self) -> &'static str {
+        match self {
+            Relevance::Irrelevant => "Irrelevant",
+            Relevance::PossiblyRelevant => "Possibly relevant",
+            Relevance::Relevant => "Relevant",
+        }
+    }
+}
+
+/// Whether the announcement was marked [`Relevance::Relevant`] by [`Fleet::annotate`].
+pub fn is_relevant(a: &Article) -> bool {
+    a.categories
+        .iter()
+        .any(|c| c.scheme == RELEVANCE_SCHEME && c.term == Relevance::Relevant.term())
+}
+
+// 実際に運用しているWindowsの組み合わせ
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Segment {
+    name: String,
+    // classifyのproductのterm (例: windows-11)
+    product: String,
+    versions: Vec<String>,
+    editions: Vec<String>,
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// The combinations of products, versions and editions of Windows in use, called segments.
+#[derive(Debug)]
+pub struct Fleet {
+    segments: Vec<Segment>,
+    edition: Regex,
+}
+impl Fleet {
+    pub(crate) fn new(segments: Vec<Segment>) -> Self {
+        Fleet {
+            segments,
+            edition: Regex::new(
+                r"\b(Home|Pro|Enterprise|Education|IoT|LTSC|Datacenter|Standard|Essentials)\b",
+            )
+            .unwrap(),
+        }
+    }
+    /// Reads one `[name]` section per segment, with its `product` and the optional `versions`
+    /// and `editions` as comma separated lists.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut segments = Vec::new();
+        for section in Ini::parse(s)?.sections_only("segment")? {
+            let mut segment = Segment {
+                name: section.name,
+                product: String::new(),
+                versions: Vec::new(),
+                editions: Vec::new(),
+            };
+            for e in &section.entries {
+                match e.key.as_str() {
+                    "product" => segment.product = e.value.to_lowercase(),
+                    "versions" => segment.versions = list(&e.value),
+                    "editions" => segment.editions = list(&e.value),
+                    k => return Err(config::error(e.line, &format!("unknown key: {}", k))),
+                }
+            }
+            if segment.name.is_empty() || segment.product.is_empty() {
+                return Err(ParseError::new(format!(
+                    "segment [{}] needs a name and a product",
+                    segment.name
+                )));
+            }
+            segments.push(segment);
+        }
+        Ok(Fleet::new(segments))
+    }
+    fn assess_segment(&self, segment: &Segment, a: &Article, text: &str) -> Relevance {
+        let products: Vec<&Category> = a
+            .categories
+            .iter()
+            .filter(|c| c.scheme == PRODUCT_SCHEME)
+            .collect();
+        let Some(product) = products.iter().find(|c| c.term == segment.product) else {
+            // 他の製品だけについてのお知らせ
+            return if products.is_empty() {
+                Relevance::PossiblyRelevant
+            } else {
+                Relevance::Irrelevant
+            };
+        };
+        let editions: Vec<String> = self
+            .edition
+            .find_iter(text)
+            .map(|m| m.as_str().to_lowercase())
+            .collect();
+        if !segment.editions.is_empty()
+            && !editions.is_empty()
+            && !editions.iter().any(|e| segment.editions.contains(e))
+        {
+            return Relevance::Irrelevant;
+        }
+        if segment.versions.is_empty() {
+            return Relevance::Relevant;
+        }
+        let lower = text.to_lowercase();
+        let label = product.label.to_lowercase();
+        // Windows Serverの2022のように、versionsとして抽出されないものは製品名の後ろを見る
+        let mentions = |v: &String| {
+            a.versions.iter().any(|av| av.to_lowercase() == *v)
+                || lower.contains(&format!("{} {}", label, v))
+        };
+        if segment.versions.iter().any(mentions) {
+            Relevance::Relevant
+        } else if a.versions.is_empty() {
+            Relevance::PossiblyRelevant
+        } else {
+            Relevance::Irrelevant
+        }
+    }
+    /// Adds the relevance of the announcement and the segments it affects as categories.
+    pub fn annotate(&self, a: &mut Article) -> Relevance {
+        let text = text::normalize(&format!("{}\n{}", a.title, a.body_text()));
+        let assessed: Vec<(&Segment, Relevance)> = self
+            .segments
+            .iter()
+            .map(|s| (s, self.assess_segment(s, a, &text)))
+            .collect();
+        let relevance = assessed
+            .iter()
+            .map(|(_, r)| *r)
+            .max()
+            .unwrap_or(Relevance::Irrelevant);
+        a.categories.push(Category {
+            term: relevance.term().to_string(),
+            scheme: RELEVANCE_SCHEME.to_string(),
+            label: relevance.label().to_string(),
+        });
+        if relevance != Relevance::Irrelevant {
+            for (s, _) in assessed.iter().filter(|(_, r)| *r == relevance) {
+                a.categories.push(Category {
+                    term: text::slug(&s.name),
+                    scheme: SEGMENT_SCHEME.to_string(),
+                    label: s.name.clone(),
+                });
+            }
+        }
+        relevance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::classify::Classifier;
+    use crate::metadata::Extractor;
+    use crate::testing;
+    const PROFILE: &str = "
+# 社内の端末
+[desktops]
+product = windows-11
+versions = 22H2, 23H2
+editions = Enterprise, Education
+
+[Legacy PCs]
+product = Windows-10
+versions = 22H2
+
+[servers]
+product = windows-server
+versions = 2019, 2022
+";
+    fn annotate(title: &str, body: &str) -> (Relevance, Vec<String>) {
+        let mut a = Article {
+            title: title.to_string(),
+            ..testing::article("1", body)
+        };
+        a.categories = Classifier::default().classify(&a);
+        Extractor::default().extract(&mut a);
+        let relevance = Fleet::parse(PROFILE).unwrap().annotate(&mut a);
+        let segments = a
+            .categories
+            .into_iter()
+            .filter(|c| c.scheme == SEGMENT_SCHEME)
+            .map(|c| c.term)
+            .collect();
+        (relevance, segments)
+    }
+    #[test]
+    fn assess() {
+        assert_eq!(
+            annotate(
+                "Reminder: End of servicing for Windows 10, version 21H2",
+                "<div>Home, Pro, Enterprise and Education editions</div>"
+            ),
+            (Relevance::Irrelevant, vec![])
+        );
+        assert_eq!(
+            annotate(
+                "Take action: June 2023 security update is now available",
+                "<div>for Windows 11 and all supported versions of Windows 10</div>"
+            ),
+            (
+                Relevance::PossiblyRelevant,
+                vec!["desktops".to_string(), "legacy-pcs".to_string()]
+            )
+        );
+        assert_eq!(
+            annotate(
+                "Windows 11, version 22H2 and Windows Server 2022 known issues",
+                "<div>Windows 11 Home and Pro are not affected. Enterprise only.</div>"
+            ),
+            (
+                Relevance::Relevant,
+                vec!["desktops".to_string(), "servers".to_string()]
+            )
+        );
+        assert_eq!(
+            annotate("Windows 11 Home and Pro only", "<div>version 22H2</div>"),
+            (Relevance::Irrelevant, vec![])
+        );
+        assert_eq!(
+            annotate("Netlogon and Kerberos hardening", ""),
+            (
+                Relevance::PossiblyRelevant,
+                vec![
+                    "desktops".to_string(),
+                    "legacy-pcs".to_string(),
+                    "servers".to_string()
+                ]
+            )
+        );
+    }
+    #[test]
+    fn parse_invalid() {
+        assert!(Fleet::parse("product = windows-11").is_err());
+        assert!(Fleet::parse("[a]\nproduct windows-11").is_err());
+        assert!(Fleet::parse("[a]\nproduct = windows-11\ncolor = blue").is_err());
+        assert!(Fleet::parse("[a]\nversions = 22H2").is_err());
+    }
+}
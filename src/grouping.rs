@@ -0,0 +1,180 @@
+use super::article::{push_unique, Article};
+use super::render::MESSAGE_CENTER_URL;
+
+use chrono::{DateTime, Datelike};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    // ISO 8601の週 (月曜始まり)
+    Week,
+}
+impl Period {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "day" => Some(Period::Day),
+            "week" => Some(Period::Week),
+            _ => None,
+        }
+    }
+}
+
+// 1日または1週間のお知らせを、目次と本文を並べた1つのエントリにまとめる
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grouping {
+    period: Period,
+    tz: Tz,
+}
+impl Grouping {
+    pub fn new(period: Period, tz: Tz) -> Self {
+        Grouping { period, tz }
+    }
+    // 日付が読めないお知らせは、そのまま1つのエントリとして残す
+    fn key(&self, a: &Article) -> Option<String> {
+        let date = DateTime::parse_from_rfc3339(&a.date)
+            .ok()?
+            .with_timezone(&self.tz);
+        Some(match self.period {
+            Period::Day => format!("day-{}", date.format("%Y-%m-%d")),
+            Period::Week => {
+                let week = date.iso_week();
+                format!("week-{}-W{:02}", week.year(), week.week())
+            }
+        })
+    }
+    fn title(&self, key: &str, count: usize) -> String {
+        let (kind, period) = key.split_once('-').unwrap_or_default();
+        format!(
+            "Announcements of {}{} ({})",
+            if kind == "week" { "week " } else { "" },
+            period,
+            count
+        )
+    }
+    fn merge(&self, key: String, mut members: Vec<Article>) -> Article {
+        members.sort_by(|a, b| b.date.cmp(&a.date));
+        let mut toc = String::from("<ul>");
+        let mut sections = String::new();
+        for a in &members {
+            let anchor = format!("wmc-{}", a.id);
+            toc += &format!(
+                "<li><a href=\"#{}\">{}</a></li>",
+                anchor,
+                html_escape::encode_text(&a.title)
+            );
+            sections += &format!(
+                "<h2 id=\"{}\"><a href=\"{}\">{}</a></h2>{}",
+                anchor,
+                html_escape::encode_double_quoted_attribute(&a.url),
+                html_escape::encode_text(&a.title),
+                a.body
+            );
+        }
+        toc += "</ul>";
+        let mut ret = Article::new(
+            key.clone(),
+            MESSAGE_CENTER_URL.to_string(),
+            "alternate",
+            self.title(&key, members.len()),
+            members[0].date.clone(),
+            toc + &sections,
+        );
+        ret.summary = members
+            .iter()
+            .map(|a| a.title.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+        for a in &members {
+            push_unique(&mut ret.categories, a.categories.iter().cloned());
+            push_unique(&mut ret.kbs, a.kbs.iter().cloned());
+            push_unique(&mut ret.builds, a.builds.iter().cloned());
+            push_unique(&mut ret.versions, a.versions.iter().cloned());
+            push_unique(&mut ret.cves, a.cves.iter().cloned());
+        }
+        ret
+    }
+    // 新しい期間から順に返す。各エントリのdateは、含まれる最も新しいお知らせのdate
+    pub fn group(&self, articles: Vec<Article>) -> Vec<Article> {
+        let mut groups: BTreeMap<String, Vec<Article>> = BTreeMap::new();
+        let mut ungrouped = Vec::new();
+        for a in articles {
+            match self.key(&a) {
+                Some(key) => groups.entry(key).or_default().push(a),
+                None => ungrouped.push(a),
+            }
+        }
+        let mut ret: Vec<Article> = groups
+            .into_iter()
+            .map(|(key, members)| self.merge(key, members))
+            .collect();
+        ret.sort_by(|a, b| b.date.cmp(&a.date));
+        ret.extend(ungrouped);
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::article::Category;
+    use crate::testing;
+    fn article(id: &str, date: &str) -> Article {
+        let mut a = Article {
+            url: format!("https://support.microsoft.com/help/{}", id),
+            title: format!("Title <{}>", id),
+            date: date.to_string(),
+            ..testing::article(id, &format!("<div>Body {}</div>", id))
+        };
+        a.categories = vec![Category {
+            term: "reminder".to_string(),
+            scheme: "https://example.com/type".to_string(),
+            label: "Reminder".to_string(),
+        }];
+        a.kbs = vec![id.to_string()];
+        a
+    }
+    fn articles() -> Vec<Article> {
+        vec![
+            // 東京では6月14日
+            article("3", "2023-06-13T17:00:00Z"),
+            article("2", "2023-06-13T01:00:00Z"),
+            article("1", "2023-06-11T23:00:00Z"),
+            article("0", "unknown"),
+        ]
+    }
+    #[test]
+    fn group_by_day() {
+        let grouped = Grouping::new(Period::Day, chrono_tz::Asia::Tokyo).group(articles());
+        let ids: Vec<&str> = grouped.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["day-2023-06-14", "day-2023-06-13", "day-2023-06-12", "0"]
+        );
+        let grouped = Grouping::new(Period::Day, chrono_tz::UTC).group(articles());
+        let day = &grouped[0];
+        assert_eq!(day.id, "day-2023-06-13");
+        assert_eq!(day.title, "Announcements of 2023-06-13 (2)");
+        assert_eq!(day.date, "2023-06-13T17:00:00Z");
+        assert_eq!(
+            day.body,
+            r##"<ul><li><a href="#wmc-3">Title &lt;3&gt;</a></li><li><a href="#wmc-2">Title &lt;2&gt;</a></li></ul><h2 id="wmc-3"><a href="https://support.microsoft.com/help/3">Title &lt;3&gt;</a></h2><div>Body 3</div><h2 id="wmc-2"><a href="https://support.microsoft.com/help/2">Title &lt;2&gt;</a></h2><div>Body 2</div>"##
+        );
+        assert_eq!(day.summary, "Title <3>; Title <2>");
+        assert_eq!(day.categories.len(), 1);
+        assert_eq!(day.kbs, ["3", "2"]);
+    }
+    #[test]
+    fn group_by_week() {
+        let grouped = Grouping::new(Period::Week, chrono_tz::UTC).group(articles());
+        let ids: Vec<&str> = grouped.iter().map(|a| a.id.as_str()).collect();
+        // 2023-06-11は日曜日なので前の週
+        assert_eq!(ids, ["week-2023-W24", "week-2023-W23", "0"]);
+        assert_eq!(grouped[0].title, "Announcements of week 2023-W24 (2)");
+        // 年をまたぐ週はISO週の年になる
+        let grouped = Grouping::new(Period::Week, chrono_tz::UTC)
+            .group(vec![article("4", "2021-01-01T00:00:00Z")]);
+        assert_eq!(grouped[0].id, "week-2020-W53");
+    }
+}
@@ -0,0 +1,166 @@
+// https://www.rfc-editor.org/rfc/rfc5545
+use chrono::NaiveDate;
+
+fn escaped(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => ret += "\\\\",
+            ';' => ret += "\\;",
+            ',' => ret += "\\,",
+            '\n' => ret += "\\n",
+            '\r' => {}
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+// 1行は75オクテットまでなので、それを超える場合は空白から始まる継続行に折り返す
+fn content_line(name: &str, value: &str) -> String {
+    let line = format!("{}:{}", name, value);
+    let mut ret = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            ret += "\r\n ";
+            width = 1;
+        }
+        ret.push(c);
+        width += c.len_utf8();
+    }
+    ret += "\r\n";
+    ret
+}
+
+fn date(d: &NaiveDate) -> String {
+    d.format("%Y%m%d").to_string()
+}
+
+pub struct Event {
+    uid: String,
+    dtstamp: String,
+    start: NaiveDate,
+    end: NaiveDate,
+    summary: String,
+    description: Option<String>,
+    url: Option<String>,
+    properties: Vec<(String, String)>,
+}
+impl Event {
+    // dtstampはYYYYMMDDTHHMMSSZ形式
+    pub fn new(
+        uid: String,
+        dtstamp: String,
+        start: NaiveDate,
+        end: NaiveDate,
+        summary: String,
+    ) -> Self {
+        Event {
+            uid,
+            dtstamp,
+            start,
+            end,
+            summary,
+            description: None,
+            url: None,
+            properties: Vec::new(),
+        }
+    }
+    pub fn description(self, description: String) -> Self {
+        Event {
+            description: Some(description),
+            ..self
+        }
+    }
+    pub fn url(self, url: String) -> Self {
+        Event {
+            url: Some(url),
+            ..self
+        }
+    }
+    // X-から始まる独自プロパティ
+    pub fn properties(self, properties: Vec<(String, String)>) -> Self {
+        Event { properties, ..self }
+    }
+    fn to_ics(&self) -> String {
+        let mut ret = content_line("BEGIN", "VEVENT");
+        ret += &content_line("UID", &escaped(&self.uid));
+        ret += &content_line("DTSTAMP", &self.dtstamp);
+        ret += &content_line("DTSTART;VALUE=DATE", &date(&self.start));
+        ret += &content_line("DTEND;VALUE=DATE", &date(&self.end));
+        ret += &content_line("SUMMARY", &escaped(&self.summary));
+        if let Some(description) = &self.description {
+            ret += &content_line("DESCRIPTION", &escaped(description));
+        }
+        if let Some(url) = &self.url {
+            ret += &content_line("URL;VALUE=URI", url);
+        }
+        for (name, value) in &self.properties {
+            ret += &content_line(name, &escaped(value));
+        }
+        ret += &content_line("END", "VEVENT");
+        ret
+    }
+}
+
+pub struct Calendar {
+    name: String,
+    source: Option<String>,
+    event: Vec<Event>,
+}
+impl Calendar {
+    pub fn new(name: String) -> Self {
+        Calendar {
+            name,
+            source: None,
+            event: Vec::new(),
+        }
+    }
+    // カレンダー自体を取得できるURL
+    pub fn source(self, source: String) -> Self {
+        Calendar {
+            source: Some(source),
+            ..self
+        }
+    }
+    pub fn event(self, event: Vec<Event>) -> Self {
+        Calendar { event, ..self }
+    }
+    pub fn to_ics(&self) -> String {
+        let mut ret = content_line("BEGIN", "VCALENDAR");
+        ret += &content_line("VERSION", "2.0");
+        ret += &content_line(
+            "PRODID",
+            "-//yumetodo//unofficial-windows-message-center-rss//EN",
+        );
+        ret += &content_line("CALSCALE", "GREGORIAN");
+        ret += &content_line("NAME", &escaped(&self.name));
+        ret += &content_line("X-WR-CALNAME", &escaped(&self.name));
+        if let Some(source) = &self.source {
+            ret += &content_line("SOURCE;VALUE=URI", source);
+        }
+        for e in &self.event {
+            ret += &e.to_ics();
+        }
+        ret += &content_line("END", "VCALENDAR");
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn escape_and_fold() {
+        assert_eq!(escaped("a,b;c\\d\r\ne"), "a\\,b\\;c\\\\d\\ne");
+        let line = content_line("SUMMARY", &"あ".repeat(30));
+        let lines: Vec<&str> = line.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.len() <= 75));
+        assert_eq!(
+            format!("{}{}", lines[0], &lines[1][1..]),
+            format!("SUMMARY:{}", "あ".repeat(30))
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+// feed.rsと同じく、JSONも小さいので手で書き出す
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+impl Json {
+    pub fn object<K: Into<String>>(members: Vec<(K, Json)>) -> Self {
+        Json::Object(members.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+    pub fn strings<S: AsRef<str>>(v: &[S]) -> Self {
+        Json::Array(v.iter().map(|s| s.as_ref().into()).collect())
+    }
+}
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Json::Null, Into::into)
+    }
+}
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as i64)
+    }
+}
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            // </script>等に埋め込まれても安全なように<も逃がす
+            '<' => f.write_str("\\u003c")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_str(f, s),
+            Json::Array(v) => {
+                f.write_str("[")?;
+                for (i, e) in v.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", e)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(members) => {
+                f.write_str("{")?;
+                for (i, (k, v)) in members.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write_str(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn to_string() {
+        let v = Json::object(vec![
+            ("title", "Take \"action\"\n<b>".into()),
+            ("count", 3usize.into()),
+            ("summary", Json::from(None::<String>)),
+            ("tags", Json::Array(vec!["a".into(), "b".into()])),
+            ("ok", true.into()),
+            ("empty", Json::object(Vec::<(String, Json)>::new())),
+        ]);
+        assert_eq!(
+            v.to_string(),
+            r#"{"title":"Take \"action\"\n\u003cb>","count":3,"summary":null,"tags":["a","b"],"ok":true,"empty":{}}"#
+        );
+    }
+}
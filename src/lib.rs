@@ -0,0 +1,84 @@
+//! Parse the [Windows message center] and turn its announcements into feeds.
+//!
+//! The binary of this package is a thin wrapper around this library. Other tools can use
+//! [`parser::Parser`] to read the announcements from the page, and write them with the
+//! functions of [`render`] or the builders of [`feed`].
+//!
+//! The parser runs the body of each announcement through optional stages: it can strip
+//! boilerplate with [`boilerplate::Boilerplate`], sanitize the HTML with
+//! [`sanitize::Sanitizer`], summarize it with [`summary::Summarizer`], and mark the
+//! relevance to a set of machines with [`fleet::Fleet`]. The lists these stages read report
+//! their mistakes as [`ParseError`].
+//!
+//! ```no_run
+//! use unofficial_windows_message_center_rss::parser::Parser;
+//! use unofficial_windows_message_center_rss::render::{self, ContentType};
+//!
+//! // render::MESSAGE_CENTER_URLを保存したもの
+//! let html = std::fs::read_to_string("page.html")?;
+//! let parser = Parser::new("https://example.com/atom.xml", "https://learn.microsoft.com");
+//! let articles = parser.parse(&html);
+//! let feed = render::atom(
+//!     "https://example.com/atom.xml",
+//!     articles,
+//!     "2023-06-14T00:00:00Z".to_string(),
+//!     ContentType::Html,
+//! );
+//! println!("{}", feed.to_xml());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! With the `serde` feature, [`article::Article`] and the feed model implement `Serialize` and
+//! `Deserialize`, and `schema` reads and writes them as versioned JSON or NDJSON.
+//!
+//! The modules listed in the documentation follow semantic versioning. [`app`] only exists
+//! for the binary and may change in any release; the state file, fetching and the other
+//! features of the command are not part of the library.
+//!
+//! [Windows message center]: https://learn.microsoft.com/en-us/windows/release-health/windows-message-center
+
+#![warn(missing_docs)]
+
+pub mod article;
+pub mod boilerplate;
+pub mod feed;
+pub mod fleet;
+pub mod parser;
+pub mod render;
+pub mod sanitize;
+#[cfg(feature = "serde")]
+pub mod schema;
+pub mod summary;
+
+pub use config::ParseError;
+
+#[doc(hidden)]
+pub mod app;
+
+mod backfill;
+mod classify;
+mod cli;
+mod config;
+mod deadline;
+mod digest;
+mod feed_reader;
+mod fetch;
+mod filter;
+mod grouping;
+mod hash;
+mod ical;
+mod json;
+mod markdown;
+mod metadata;
+mod rules;
+mod search;
+mod server;
+mod state;
+mod store;
+mod terminal;
+#[cfg(test)]
+mod testing;
+mod text;
+mod warc;
+mod webhook;
+mod xhtml;
@@ -0,0 +1,440 @@
+use super::article::Article;
+
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const BLOCKS: [&str; 16] = [
+    "div",
+    "p",
+    "ul",
+    "ol",
+    "li",
+    "table",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "pre",
+    "blockquote",
+    "hr",
+    "section",
+];
+
+fn is_block(node: NodeRef<Node>) -> bool {
+    node.value()
+        .as_element()
+        .is_some_and(|e| BLOCKS.contains(&e.name()))
+}
+
+fn escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '*' | '_' | '[' | ']' | '`' | '<' | '>' | '|') {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+// 前後の空白は区切り記号の外に出す (** text**は強調にならない)
+fn wrap(inner: &str, open: &str, close: &str) -> String {
+    let trimmed = inner.trim();
+    if trimmed.is_empty() {
+        return inner.to_string();
+    }
+    let start = inner.len() - inner.trim_start().len();
+    let end = start + trimmed.len();
+    format!(
+        "{}{}{}{}{}",
+        &inner[..start],
+        open,
+        trimmed,
+        close,
+        &inner[end..]
+    )
+}
+
+fn inline_children(node: NodeRef<Node>) -> String {
+    node.children().map(inline).collect()
+}
+
+fn inline(node: NodeRef<Node>) -> String {
+    let e = match node.value() {
+        Node::Text(t) => {
+            // 連続する空白 (改行やNBSPを含む) は1つの空白にする
+            let mut ret = String::new();
+            for (i, word) in t.split(char::is_whitespace).enumerate() {
+                if i > 0 && !ret.ends_with(' ') {
+                    ret.push(' ');
+                }
+                ret += &escape(word);
+            }
+            return ret;
+        }
+        Node::Element(e) => e,
+        _ => return String::new(),
+    };
+    match e.name() {
+        "br" => "\\\n".to_string(),
+        "b" | "strong" => wrap(&inline_children(node), "**", "**"),
+        "i" | "em" => wrap(&inline_children(node), "*", "*"),
+        "s" | "del" => wrap(&inline_children(node), "~~", "~~"),
+        // Markdownには下線がないのでHTMLのまま残す
+        "u" => wrap(&inline_children(node), "<u>", "</u>"),
+        "code" => {
+            let text: String = node
+                .descendants()
+                .filter_map(|n| n.value().as_text())
+                .map(|t| &**t)
+                .collect();
+            wrap(&text, "`", "`")
+        }
+        "a" => {
+            let inner = inline_children(node);
+            match e.attr("href") {
+                Some(href) if !inner.trim().is_empty() => wrap(
+                    &inner,
+                    "[",
+                    &format!("]({})", href.replace(' ', "%20").replace(')', "%29")),
+                ),
+                _ => inner,
+            }
+        }
+        "img" => format!(
+            "![{}]({})",
+            escape(e.attr("alt").unwrap_or_default()),
+            e.attr("src").unwrap_or_default()
+        ),
+        // 段落の中に現れたブロック要素は、段落を区切らずに中身だけを並べる
+        _ => blocks(node).join(" "),
+    }
+}
+
+fn paragraph(s: &str) -> Option<String> {
+    let lines: Vec<String> = s
+        .split('\n')
+        .map(|l| {
+            let hard_break = l.ends_with('\\');
+            let l = l.trim_end_matches('\\').split_whitespace();
+            let l = l.collect::<Vec<_>>().join(" ");
+            if hard_break {
+                l + "\\"
+            } else {
+                l
+            }
+        })
+        .collect();
+    // 末尾の改行は意味がない
+    let mut ret = lines.join("\n");
+    while ret.ends_with('\\') || ret.ends_with('\n') {
+        ret.pop();
+    }
+    let ret = ret.trim_start_matches(['\n', '\\']).to_string();
+    (!ret.trim().is_empty()).then_some(ret)
+}
+
+fn indent(s: &str, first: &str) -> String {
+    let rest = " ".repeat(first.len());
+    s.lines()
+        .enumerate()
+        .map(|(i, l)| match (i, l.is_empty()) {
+            (0, _) => format!("{}{}", first, l),
+            (_, true) => String::new(),
+            _ => format!("{}{}", rest, l),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn list(node: NodeRef<Node>, ordered: bool) -> Option<String> {
+    let items: Vec<String> = node
+        .children()
+        .filter(|c| c.value().as_element().is_some_and(|e| e.name() == "li"))
+        .enumerate()
+        .map(|(i, li)| {
+            let marker = if ordered {
+                format!("{}. ", i + 1)
+            } else {
+                "- ".to_string()
+            };
+            indent(&blocks(li).join("\n"), &marker)
+        })
+        .collect();
+    (!items.is_empty()).then(|| items.join("\n"))
+}
+
+fn rows(node: NodeRef<Node>) -> Vec<NodeRef<Node>> {
+    let mut ret = Vec::new();
+    for child in node.children() {
+        match child.value().as_element().map(|e| e.name()) {
+            Some("tr") => ret.push(child),
+            Some("thead" | "tbody" | "tfoot") => ret.extend(rows(child)),
+            _ => {}
+        }
+    }
+    ret
+}
+
+fn table(node: NodeRef<Node>) -> Option<String> {
+    let rows: Vec<Vec<String>> = rows(node)
+        .into_iter()
+        .map(|tr| {
+            tr.children()
+                .filter(|c| {
+                    c.value()
+                        .as_element()
+                        .is_some_and(|e| matches!(e.name(), "td" | "th"))
+                })
+                .map(|cell| blocks(cell).join("<br>").replace('\n', "<br>"))
+                .collect()
+        })
+        .collect();
+    let columns = rows.iter().map(Vec::len).max().filter(|n| *n > 0)?;
+    let line = |cells: &[String]| {
+        let mut ret = String::from("|");
+        for i in 0..columns {
+            ret += &format!(" {} |", cells.get(i).map_or("", String::as_str));
+        }
+        ret
+    };
+    let mut ret = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+    ret.extend(rows[1..].iter().map(|r| line(r)));
+    Some(ret.join("\n"))
+}
+
+fn block(node: NodeRef<Node>) -> Option<String> {
+    let e = node.value().as_element()?;
+    match e.name() {
+        "ul" => list(node, false),
+        "ol" => list(node, true),
+        "table" => table(node),
+        "hr" => Some("---".to_string()),
+        "pre" => {
+            let text: String = node
+                .descendants()
+                .filter_map(|n| n.value().as_text())
+                .map(|t| &**t)
+                .collect();
+            Some(format!("```\n{}\n```", text.trim_matches('\n')))
+        }
+        "blockquote" => {
+            let inner = blocks(node).join("\n\n");
+            (!inner.is_empty()).then(|| {
+                inner
+                    .lines()
+                    .map(|l| format!("> {}", l).trim_end().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+        h if h.len() == 2 && h.starts_with('h') => {
+            let level = h[1..].parse().ok()?;
+            paragraph(&inline_children(node).replace("\\\n", " "))
+                .map(|s| format!("{} {}", "#".repeat(level), s))
+        }
+        // divやpやliなど、他のブロックを含みうる要素
+        _ => {
+            let inner = blocks(node).join("\n\n");
+            (!inner.is_empty()).then_some(inner)
+        }
+    }
+}
+
+// 子をブロックの列に変換する。ブロック要素の間に続くインラインの要素は1つの段落にまとめる
+fn blocks(node: NodeRef<Node>) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut current = String::new();
+    for child in node.children() {
+        if is_block(child) {
+            ret.extend(paragraph(&current));
+            current.clear();
+            ret.extend(block(child));
+        } else {
+            current += &inline(child);
+        }
+    }
+    ret.extend(paragraph(&current));
+    ret
+}
+
+pub fn from_html(body: &str) -> String {
+    let html = Html::parse_fragment(body);
+    blocks(*html.root_element()).join("\n\n")
+}
+
+fn yaml_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// YAMLのfront matterに続けて、タイトルと本文を書く
+pub fn document(a: &Article) -> String {
+    let mut ret = String::from("---\n");
+    ret += &format!("id: {}\n", yaml_string(&a.id));
+    ret += &format!("title: {}\n", yaml_string(&a.title));
+    ret += &format!("date: {}\n", a.date);
+    ret += &format!("url: {}\n", yaml_string(&a.url));
+    if a.categories.is_empty() {
+        ret += "categories: []\n";
+    } else {
+        ret += "categories:\n";
+        for c in &a.categories {
+            ret += &format!("  - {}\n", yaml_string(&c.term));
+        }
+    }
+    ret += "---\n\n";
+    ret += &format!("# {}\n", escape(&a.title));
+    let body = from_html(&a.body);
+    if !body.is_empty() {
+        ret += "\n";
+        ret += &body;
+        ret += "\n";
+    }
+    ret
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ExportSummary {
+    pub written: usize,
+    pub unchanged: usize,
+    // ファイル名に使えないidのお知らせ
+    pub skipped: Vec<String>,
+}
+
+// idはバックフィルしたフィードやJSONからも来るので、DIRの外を指さない文字だけを受け付ける
+fn is_file_name(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+}
+
+// お知らせごとに{id}.mdを書く。内容が同じファイルは書き換えない
+pub fn export<'a, I: IntoIterator<Item = &'a Article>>(
+    articles: I,
+    dir: &Path,
+) -> io::Result<ExportSummary> {
+    fs::create_dir_all(dir)?;
+    let mut ret = ExportSummary::default();
+    for a in articles {
+        if !is_file_name(&a.id) {
+            ret.skipped.push(a.id.clone());
+            continue;
+        }
+        let path = dir.join(format!("{}.md", a.id));
+        let doc = document(a);
+        if fs::read_to_string(&path).is_ok_and(|old| old == doc) {
+            ret.unchanged += 1;
+            continue;
+        }
+        fs::write(&path, doc)?;
+        ret.written += 1;
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::article::Category;
+    use crate::testing;
+    #[test]
+    fn convert() {
+        let body = r#"<div>
+    The June 2023 security update is <b>now
+    available</b>. See the&nbsp;<a href="https://support.microsoft.com/help/5027231"
+        >Windows 11 <u>update history</u></a
+    >&nbsp;page.<br>Use *care*.</div>
+<div><strong>Highlights:&nbsp;</strong></div>
+<ul>
+    <li>First <i>item</i></li>
+    <li>Second<ol><li>Nested</li><li><div>Block</div></li></ol></li>
+</ul>
+<table>
+    <tr><th>Version</th><th>Build</th></tr>
+    <tr><td>22H2</td><td>22621.1848 | <a href="https://example.com">notes</a></td></tr>
+    <tr><td>21H2</td></tr>
+</table>"#;
+        assert_eq!(
+            from_html(body),
+            r#"The June 2023 security update is **now available**. See the [Windows 11 <u>update history</u>](https://support.microsoft.com/help/5027231) page.\
+Use \*care\*.
+
+**Highlights:**
+
+- First *item*
+- Second
+  1. Nested
+  2. Block
+
+| Version | Build |
+| --- | --- |
+| 22H2 | 22621.1848 \| [notes](https://example.com) |
+| 21H2 |  |"#
+        );
+    }
+    #[test]
+    fn export_idempotently() {
+        let mut a = Article {
+            title: "Take action: \"June\" 2023 update".to_string(),
+            ..testing::article("3110", "<div>Body</div>")
+        };
+        a.categories = vec![Category {
+            term: "take-action".to_string(),
+            scheme: "https://example.com/type".to_string(),
+            label: "Take action".to_string(),
+        }];
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("kb");
+        assert_eq!(
+            export([&a], &out).unwrap(),
+            ExportSummary {
+                written: 1,
+                unchanged: 0,
+                skipped: Vec::new()
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(out.join("3110.md")).unwrap(),
+            r#"---
+id: "3110"
+title: "Take action: \"June\" 2023 update"
+date: 2023-06-13T17:00:00Z
+url: "https://support.microsoft.com/help/5027231"
+categories:
+  - "take-action"
+---
+
+# Take action: "June" 2023 update
+
+Body
+"#
+        );
+        assert_eq!(
+            export([&a], &out).unwrap(),
+            ExportSummary {
+                written: 0,
+                unchanged: 1,
+                skipped: Vec::new()
+            }
+        );
+        a.body = "<div>Updated</div>".to_string();
+        assert_eq!(export([&a], &out).unwrap().written, 1);
+        assert!(fs::read_to_string(out.join("3110.md"))
+            .unwrap()
+            .ends_with("Updated\n"));
+
+        // DIRの外に書くおそれのあるidは書き出さない
+        for id in ["../escape", "/tmp/x", "a.b", ""] {
+            a.id = id.to_string();
+            assert_eq!(export([&a], &out).unwrap().skipped, [id]);
+        }
+        assert!(!dir.path().join("escape.md").exists());
+        assert_eq!(fs::read_dir(&out).unwrap().count(), 1);
+    }
+}
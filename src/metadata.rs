@@ -0,0 +1,136 @@
+use super::article::{push_unique, Article};
+
+use regex::Regex;
+use scraper::{Html, Selector};
+
+#[derive(Debug)]
+pub struct Extractor {
+    kb_in_text: Regex,
+    kb_in_url: Regex,
+    build: Regex,
+    version: Regex,
+    cve: Regex,
+    selector_link: Selector,
+}
+
+impl Default for Extractor {
+    fn default() -> Self {
+        Extractor {
+            kb_in_text: Regex::new(r"\bKB\s?(\d{6,7})\b").unwrap(),
+            kb_in_url: Regex::new(
+                r"(?i)^https?://support\.microsoft\.com/(?:[a-z]{2}-[a-z]{2}/)?(?:help|kb)/(\d{6,7})\b",
+            )
+            .unwrap(),
+            // 22621.1848のようなOSビルド
+            build: Regex::new(r"\b([12]\d{4}\.\d{1,5})\b").unwrap(),
+            // 21H2等と、"version 1809"のような古い形式
+            version: Regex::new(r"\b(\d{2}H[12])\b|\bversion\s+(1[5-9]\d{2}|20[0-4]\d)\b").unwrap(),
+            cve: Regex::new(r"(?i)\bCVE-(\d{4})-(\d{4,})\b").unwrap(),
+            selector_link: Selector::parse("a[href]").unwrap(),
+        }
+    }
+}
+impl Extractor {
+    pub fn extract(&self, a: &mut Article) {
+        let text = format!("{}\n{}", a.title, a.body_text());
+        let mut urls = vec![a.url.clone()];
+        urls.extend(
+            Html::parse_fragment(&a.body)
+                .select(&self.selector_link)
+                .filter_map(|e| e.value().attr("href"))
+                .map(String::from),
+        );
+        let mut kbs = Vec::new();
+        push_unique(
+            &mut kbs,
+            urls.iter()
+                .filter_map(|url| self.kb_in_url.captures(url))
+                .map(|c| c[1].to_string()),
+        );
+        push_unique(
+            &mut kbs,
+            self.kb_in_text
+                .captures_iter(&text)
+                .map(|c| c[1].to_string()),
+        );
+        let mut builds = Vec::new();
+        push_unique(
+            &mut builds,
+            self.build.captures_iter(&text).map(|c| c[1].to_string()),
+        );
+        let mut versions = Vec::new();
+        push_unique(
+            &mut versions,
+            self.version
+                .captures_iter(&text)
+                .map(|c| c.get(1).or_else(|| c.get(2)).unwrap().as_str().to_string()),
+        );
+        // MSRCへのリンクのURLにだけCVE番号が書かれていることもある
+        let mut cves = Vec::new();
+        for s in std::iter::once(&text).chain(urls.iter()) {
+            push_unique(
+                &mut cves,
+                self.cve
+                    .captures_iter(s)
+                    .map(|c| format!("CVE-{}-{}", &c[1], &c[2])),
+            );
+        }
+        a.kbs = kbs;
+        a.builds = builds;
+        a.versions = versions;
+        a.cves = cves;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    fn extract(url: &str, title: &str, body: &str) -> Article {
+        let mut a = Article {
+            url: url.to_string(),
+            title: title.to_string(),
+            ..testing::article("1", body)
+        };
+        Extractor::default().extract(&mut a);
+        a
+    }
+    #[test]
+    fn extract_from_links_and_text() {
+        let a = extract(
+            "https://support.microsoft.com/help/5027231",
+            "Take action: June 2023 security update is now available",
+            r#"<div>see the <a href="https://support.microsoft.com/help/5027231">Windows 11</a>
+            and <a href="https://support.microsoft.com/en-us/kb/5027215">Windows 10</a> pages,
+            KB5026372 (OS Build 22621.1778) and KB 5027231 for OS Builds 22621.1848 and 22631.1848.
+            Released at 2023.06.13, not a build.</div>"#,
+        );
+        assert_eq!(a.kbs, ["5027231", "5027215", "5026372"]);
+        assert_eq!(a.builds, ["22621.1778", "22621.1848", "22631.1848"]);
+        assert!(a.versions.is_empty());
+    }
+    #[test]
+    fn extract_versions() {
+        let a = extract(
+            "https://learn.microsoft.com/en-us/lifecycle/announcements/windows-10-21h2-end-of-servicing",
+            "Reminder: End of servicing for Windows 10, version 21H2 Home, Pro",
+            "<div>Windows 10, version 1809 and Windows 11, versions 22H2 and 23H2, also 21H2. Not 2023.</div>",
+        );
+        assert!(a.kbs.is_empty());
+        assert_eq!(a.versions, ["21H2", "1809", "22H2", "23H2"]);
+    }
+    #[test]
+    fn extract_cves() {
+        let a = extract(
+            "https://support.microsoft.com/help/5021131",
+            "Reminder: Kerberos protocol changes related to CVE-2022-37966",
+            r#"<div>Addresses cve-2022-37967 and CVE-2022-37966. See
+            <a href="https://msrc.microsoft.com/update-guide/vulnerability/CVE-2021-42287">the guide</a>.
+            Not CVE-22-1 nor CVE-2022-123.</div>"#,
+        );
+        assert_eq!(
+            a.cves,
+            ["CVE-2022-37966", "CVE-2022-37967", "CVE-2021-42287"]
+        );
+    }
+}
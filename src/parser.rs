@@ -1,4 +1,13 @@
+//! Reading the announcements from the page.
+
 use super::article::Article;
+use super::boilerplate::Boilerplate;
+use super::classify::Classifier;
+use super::fleet::Fleet;
+use super::metadata::Extractor;
+use super::sanitize::Sanitizer;
+use super::summary::Summarizer;
+use super::text;
 
 use chrono::TimeZone;
 use chrono_tz::America::Los_Angeles;
@@ -7,6 +16,11 @@ use scraper::ElementRef;
 use scraper::Html;
 use scraper::Selector;
 
+/// Reads the announcements from the HTML of the Windows message center.
+///
+/// Each announcement gets a summary, its type and product categories, and the KB numbers,
+/// builds, versions and CVE identifiers it mentions. The body is sanitized with an allow-list
+/// of elements and attributes.
 #[derive(Debug)]
 pub struct Parser {
     self_uri: String,
@@ -18,8 +32,16 @@ pub struct Parser {
     selector_b_for_self: Selector,
     selector_article_body: Selector,
     selector_article_date: Selector,
+    classifier: Classifier,
+    extractor: Extractor,
+    boilerplate: Option<Boilerplate>,
+    sanitizer: Sanitizer,
+    summarizer: Summarizer,
+    fleet: Option<Fleet>,
 }
 impl Parser {
+    /// `self_uri` is the URL of the generated feed, used as the link of announcements without
+    /// their own page. `base_url` resolves the site-relative links of the page.
     pub fn new(self_uri: &str, base_url: &str) -> Self {
         Parser {
             self_uri: self_uri.to_string(),
@@ -31,6 +53,35 @@ impl Parser {
             selector_b_for_self: Selector::parse("td[id] > b").unwrap(),
             selector_article_body: Selector::parse("td[id] > div").unwrap(),
             selector_article_date: Selector::parse("td[id] + td").unwrap(),
+            classifier: Classifier::default(),
+            extractor: Extractor::default(),
+            boilerplate: None,
+            sanitizer: Sanitizer::default(),
+            summarizer: Summarizer::default(),
+            fleet: None,
+        }
+    }
+    /// Replaces the summarizer that writes the summary of each announcement.
+    pub fn summarizer(self, summarizer: Summarizer) -> Self {
+        Parser { summarizer, ..self }
+    }
+    /// Strips the boilerplate from the bodies. Without this, the bodies keep it.
+    pub fn boilerplate(self, boilerplate: Boilerplate) -> Self {
+        Parser {
+            boilerplate: Some(boilerplate),
+            ..self
+        }
+    }
+    /// Replaces the default allow-list used to sanitize the bodies.
+    pub fn sanitizer(self, sanitizer: Sanitizer) -> Self {
+        Parser { sanitizer, ..self }
+    }
+    /// Adds a category for the relevance of each announcement to the fleet, and one for each
+    /// segment it affects.
+    pub fn fleet(self, fleet: Fleet) -> Self {
+        Parser {
+            fleet: Some(fleet),
+            ..self
         }
     }
     fn parse_url<'a>(&self, title_element: ElementRef<'a>) -> Option<(String, &'static str)> {
@@ -72,7 +123,7 @@ impl Parser {
             "alternate" => title_element.select(&self.selector_b_for_alternate).next(),
             _ => None,
         }?;
-        Some(s.text().collect())
+        Some(text::normalize(&s.text().collect::<String>()))
     }
     fn parse_line<'a>(&self, tr: ElementRef<'a>) -> Option<Article> {
         let title_element = tr.select(&self.selector_article_head).next()?;
@@ -92,11 +143,26 @@ impl Parser {
         let date = self.parse_date(date)?;
         Some(Article::new(id, url, rel, title, date, body))
     }
+    /// Returns the announcements of the page in the order of the page (newest first).
+    /// Rows which cannot be parsed are skipped.
     pub fn parse(&self, doc: &str) -> Vec<Article> {
-        let document = Html::parse_document(&doc);
+        let document = Html::parse_document(doc);
         let ret = document
             .select(&self.selector_table_line)
             .filter_map(|tr| self.parse_line(tr))
+            .map(|mut a| {
+                if let Some(boilerplate) = &self.boilerplate {
+                    a.body = boilerplate.strip(&a.body);
+                }
+                a.body = self.sanitizer.sanitize(&a.body);
+                a.summary = self.summarizer.summarize(&a.body);
+                a.categories = self.classifier.classify(&a);
+                self.extractor.extract(&mut a);
+                if let Some(fleet) = &self.fleet {
+                    fleet.annotate(&mut a);
+                }
+                a
+            })
             .collect();
         ret
     }
@@ -340,6 +406,17 @@ mod test {
         );
     }
     #[test]
+    fn parse_title_when_absolute_path() {
+        let doc = Html::parse_document(HTML_OF_ABSOLUTE_PATH);
+        let p = Parser::new("", "");
+        let tr: ElementRef<'_> = doc.select(&p.selector_table_line).next().unwrap();
+        let title_element = tr.select(&p.selector_article_head).next().unwrap();
+        assert_eq!(
+            p.parse_title(tr, title_element, "alternate").unwrap(),
+            "Reminder: End of servicing for Windows 10, version 21H2 Home, Pro, Pro Education, and Pro for Workstations"
+        );
+    }
+    #[test]
     fn parse_title_when_self() {
         let doc = Html::parse_document(HTML_OF_SELF_BOOKMARK);
         let p = Parser::new("", "");
@@ -368,7 +445,8 @@ mod test {
         );
         assert_eq!(article.date, "2022-05-24T21:00:00Z");
         let count_div = Regex::new(r"</?div>").unwrap();
-        assert_eq!(count_div.find_iter(&article.body).count(), 10);
+        // 空のdivは取り除かれる
+        assert_eq!(count_div.find_iter(&article.body).count(), 6);
         assert!(article.body.contains(
             r"The June 2023 security update release is now available
     for Windows 11 and all supported versions of Windows
@@ -392,5 +470,41 @@ mod test {
         assert!(article
             .body
             .contains("release notes video for this month's tips."));
+        assert!(!article.body.contains("target="));
+        assert!(!article.body.contains("data-linktype"));
+        assert!(article.body.contains(
+            r#"<a href="https://support.microsoft.com/help/5027231" rel="noopener noreferrer">Windows 11</a>"#
+        ));
+        let text = article.body_text();
+        assert!(text.starts_with("The June 2023 security update release is now available for Windows 11 and all supported versions of Windows 10. We recommend"));
+        assert!(
+            text.contains("accessible from the Windows 11 and Windows 10 update history pages.")
+        );
+        assert!(!text.contains(['\n', '\u{a0}']));
+        assert!(!text.contains("  "));
+        assert_eq!(
+            article.summary,
+            "The June 2023 security update release is now available for Windows 11 and all supported versions of Windows 10. We recommend that you install these updates promptly."
+        );
+        let categories: Vec<_> = article.categories.iter().map(|c| c.term.as_str()).collect();
+        assert_eq!(
+            categories,
+            ["take-action", "windows-11", "windows-10", "security-update"]
+        );
+        assert_eq!(article.kbs, ["5027231", "5027215"]);
+    }
+    #[test]
+    fn parse_with_boilerplate() {
+        let p = Parser::new("", "").boilerplate(Boilerplate::default());
+        let article = &p.parse(HTML_OF_EXTERNAL)[0];
+        assert!(!article
+            .body
+            .contains("To be informed about the latest updates"));
+        assert!(!article.body.contains("@WindowsUpdate"));
+        assert!(article
+            .body
+            .contains("Highlights for the Windows 11 update"));
+        assert!(!article.body.contains("Short on time?"));
+        assert!(!article.body.contains("&nbsp;&nbsp;</div>"));
     }
 }
@@ -0,0 +1,420 @@
+//! The feeds written from the announcements.
+
+use super::article::{self, Article};
+use super::deadline;
+use super::feed::rss::{Channel, Item};
+use super::feed::*;
+use super::grouping::Grouping;
+use super::ical::{Calendar, Event};
+use super::json::Json;
+use super::xhtml;
+
+use chrono::DateTime;
+use std::fmt;
+use std::str::FromStr;
+
+/// The page the announcements are read from.
+pub const MESSAGE_CENTER_URL: &str =
+    "https://learn.microsoft.com/en-us/windows/release-health/windows-message-center";
+const FEED_ID: &str = "https://github.com/yumetodo/unofficial-windows-message-center-rss";
+const FEED_TITLE: &str = "Windows message center - Recent announcements";
+/// The prefix of the links to the MSRC Security Update Guide, followed by a CVE identifier.
+pub const MSRC_UPDATE_GUIDE_URL: &str = "https://msrc.microsoft.com/update-guide/vulnerability/";
+/// The prefix of the extension elements of the Atom feed, such as `<wmc:kb>`.
+pub const EXTENSION_PREFIX: &str = "wmc";
+/// The namespace of the extension elements of the Atom feed.
+pub const EXTENSION_NAMESPACE: &str =
+    "https://github.com/yumetodo/unofficial-windows-message-center-rss/ns/1.0";
+
+/// The type of `<content>` of the Atom feed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ContentType {
+    /// Escaped HTML.
+    #[default]
+    Html,
+    /// XHTML embedded in the feed.
+    Xhtml,
+}
+impl FromStr for ContentType {
+    type Err = ParseContentTypeError;
+    /// Reads `html` or `xhtml`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(ContentType::Html),
+            "xhtml" => Ok(ContentType::Xhtml),
+            _ => Err(ParseContentTypeError(s.to_string())),
+        }
+    }
+}
+
+/// The error of reading a [`ContentType`] other than `html` or `xhtml`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseContentTypeError(String);
+impl fmt::Display for ParseContentTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown content type {:?} (expected html or xhtml)",
+            self.0
+        )
+    }
+}
+impl std::error::Error for ParseContentTypeError {}
+
+// フィードの出力方法の設定
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct FeedOptions {
+    pub content_type: ContentType,
+    // 設定されていれば、期間ごとのダイジェストを1エントリにする
+    pub grouping: Option<Grouping>,
+}
+impl FeedOptions {
+    pub fn group(&self, articles: Vec<Article>) -> Vec<Article> {
+        match &self.grouping {
+            Some(g) => g.group(articles),
+            None => articles,
+        }
+    }
+}
+
+fn entry_id(a: &Article) -> String {
+    format!("{}#{}", MESSAGE_CENTER_URL, a.id)
+}
+
+fn atom_categories(categories: &[article::Category]) -> Vec<Category> {
+    categories
+        .iter()
+        .map(|c| {
+            Category::new(c.term.as_str())
+                .scheme(c.scheme.as_str())
+                .label(c.label.as_str())
+        })
+        .collect()
+}
+
+fn extensions(a: &Article) -> Vec<Extension> {
+    let mut ret = Vec::new();
+    for (name, values) in [
+        ("kb", &a.kbs),
+        ("build", &a.builds),
+        ("version", &a.versions),
+    ] {
+        for v in values {
+            ret.push(Extension::new(format!("{}:{}", EXTENSION_PREFIX, name), v));
+        }
+    }
+    ret
+}
+
+fn rfc2822(date: &str) -> String {
+    DateTime::parse_from_rfc3339(date).map_or_else(|_| date.to_string(), |d| d.to_rfc2822())
+}
+
+/// Renders the announcements as an Atom feed.
+pub fn atom(
+    self_uri: &str,
+    articles: Vec<Article>,
+    updated: String,
+    content_type: ContentType,
+) -> Feed {
+    atom_with_id(FEED_ID.into(), self_uri, articles, updated, content_type)
+}
+
+// 絞り込んだフィードは別のフィードとして扱われるように、条件をidに含める
+pub(crate) fn filtered_feed_id(query: &str) -> String {
+    if query.is_empty() {
+        FEED_ID.into()
+    } else {
+        format!("{}?{}", FEED_ID, query)
+    }
+}
+
+/// Same as [`atom`] with a custom feed id.
+pub fn atom_with_id(
+    id: String,
+    self_uri: &str,
+    articles: Vec<Article>,
+    updated: String,
+    content_type: ContentType,
+) -> Feed {
+    let entries = articles
+        .into_iter()
+        .map(|a: Article| {
+            let extension = extensions(&a);
+            let id = entry_id(&a);
+            let mut link = vec![Link::new().href(a.url).rel(a.rel)];
+            link.extend(a.cves.iter().map(|cve| {
+                Link::new()
+                    .href(format!("{}{}", MSRC_UPDATE_GUIDE_URL, cve))
+                    .rel("related")
+                    .title(cve.as_str())
+            }));
+            let content: Content = match content_type {
+                ContentType::Html => a.body.into(),
+                ContentType::Xhtml => XHTMLText::from_xhtml(xhtml::from_html(&a.body)).into(),
+            };
+            let entry = Entry::new(id, a.title.into(), a.date)
+                .link(link)
+                .content(content)
+                .category(atom_categories(&a.categories))
+                .extension(extension);
+            // summaryはtype="html"なので、プレーンテキストをHTMLとしてエスケープしておく
+            match a.summary.as_str() {
+                "" => entry,
+                s => entry.summary(String::from(html_escape::encode_text(s))),
+            }
+        })
+        .collect::<Vec<Entry>>();
+    Feed::new(id, FEED_TITLE.into(), updated)
+        .author(vec![Person::new("direek"), Person::new("Microsoft")])
+        .link(vec![
+            Link::new().href(MESSAGE_CENTER_URL).type_("text/html"),
+            Link::new()
+                .href(self_uri)
+                .type_("application/atom+xml")
+                .rel("self"),
+        ])
+        .entry(entries)
+        .namespace(vec![Namespace::new(EXTENSION_PREFIX, EXTENSION_NAMESPACE)])
+}
+
+/// Renders the announcements as an RSS 2.0 channel.
+pub fn rss(self_uri: &str, articles: Vec<Article>, updated: &str) -> Channel {
+    let items = articles
+        .into_iter()
+        .map(|a: Article| {
+            let category = a
+                .categories
+                .iter()
+                .map(|c| (c.scheme.clone(), c.label.clone()))
+                .collect();
+            let id = entry_id(&a);
+            // 本文がなければ要約で代える
+            let description = if a.body.trim().is_empty() {
+                String::from(html_escape::encode_text(&a.summary))
+            } else {
+                a.body
+            };
+            Item::new(id, a.title, rfc2822(&a.date))
+                .link(a.url)
+                .description(description)
+                .category(category)
+        })
+        .collect::<Vec<Item>>();
+    Channel::new(
+        FEED_TITLE.into(),
+        MESSAGE_CENTER_URL.into(),
+        "Unofficial feed of the Windows message center".into(),
+        self_uri.into(),
+        rfc2822(updated),
+    )
+    .item(items)
+}
+
+// https://www.jsonfeed.org/version/1.1/
+pub(crate) fn json_feed(self_uri: &str, articles: Vec<Article>) -> Json {
+    let items = articles
+        .into_iter()
+        .map(|a: Article| {
+            Json::object(vec![
+                ("id", entry_id(&a).into()),
+                ("url", a.url.into()),
+                ("title", a.title.into()),
+                ("content_html", a.body.into()),
+                (
+                    "summary",
+                    (!a.summary.is_empty()).then_some(a.summary).into(),
+                ),
+                ("date_published", a.date.into()),
+                (
+                    "tags",
+                    Json::Array(
+                        a.categories
+                            .iter()
+                            .map(|c| c.term.as_str().into())
+                            .collect(),
+                    ),
+                ),
+                // JSON Feedの拡張は_から始まるキーに置く
+                (
+                    "_windows_message_center",
+                    Json::object(vec![
+                        ("kb", Json::strings(&a.kbs)),
+                        ("builds", Json::strings(&a.builds)),
+                        ("versions", Json::strings(&a.versions)),
+                        ("cves", Json::strings(&a.cves)),
+                    ]),
+                ),
+            ])
+        })
+        .collect();
+    Json::object(vec![
+        ("version", "https://jsonfeed.org/version/1.1".into()),
+        ("title", FEED_TITLE.into()),
+        ("home_page_url", MESSAGE_CENTER_URL.into()),
+        ("feed_url", self_uri.into()),
+        (
+            "authors",
+            Json::Array(vec![
+                Json::object(vec![("name", "direek".into())]),
+                Json::object(vec![("name", "Microsoft".into())]),
+            ]),
+        ),
+        ("items", Json::Array(items)),
+    ])
+}
+
+// 本文中の期日ごとに1つのVEVENTにする
+pub(crate) fn ics(self_uri: Option<&str>, articles: Vec<Article>, updated: &str) -> Calendar {
+    let extractor = deadline::Extractor::default();
+    let dtstamp = updated.replace(['-', ':'], "");
+    let events = articles
+        .iter()
+        .flat_map(|a| {
+            extractor.extract(a).into_iter().map(|d| {
+                Event::new(
+                    format!("{}/{}", entry_id(a), d.start.format("%Y%m%d")),
+                    dtstamp.clone(),
+                    d.start,
+                    d.end,
+                    a.title.clone(),
+                )
+                .description(format!(
+                    "\"{}\" ({} confidence)",
+                    d.phrase,
+                    d.confidence.as_str()
+                ))
+                .url(a.url.clone())
+                .properties(vec![(
+                    "X-WMC-CONFIDENCE".to_string(),
+                    d.confidence.as_str().to_string(),
+                )])
+            })
+        })
+        .collect();
+    let calendar = Calendar::new(FEED_TITLE.into()).event(events);
+    match self_uri {
+        Some(uri) => calendar.source(uri.into()),
+        None => calendar,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    fn articles() -> Vec<Article> {
+        let mut a = Article {
+            title: "Take action: June 2023 security update & more".to_string(),
+            ..testing::article("3110", "<div>body</div>")
+        };
+        a.categories = vec![article::Category {
+            term: "take-action".to_string(),
+            scheme: "https://example.com/type".to_string(),
+            label: "Take action".to_string(),
+        }];
+        a.kbs = vec!["5027231".to_string(), "5027215".to_string()];
+        a.builds = vec!["22621.1848".to_string()];
+        a.cves = vec!["CVE-2022-37967".to_string()];
+        a.summary = "Body & <more>".to_string();
+        vec![a]
+    }
+    #[test]
+    fn parse_content_type() {
+        assert_eq!("xhtml".parse(), Ok(ContentType::Xhtml));
+        assert_eq!(
+            "text".parse::<ContentType>().unwrap_err().to_string(),
+            "unknown content type \"text\" (expected html or xhtml)"
+        );
+    }
+    #[test]
+    fn render_atom() {
+        let xml = atom(
+            "https://example.com/atom.xml",
+            articles(),
+            "2023-06-14T00:00:00Z".to_string(),
+            ContentType::Html,
+        )
+        .to_xml();
+        assert!(xml.contains(r#"<content type="html">&lt;div&gt;body&lt;/div&gt;</content>"#));
+        assert!(xml.contains(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:wmc="https://github.com/yumetodo/unofficial-windows-message-center-rss/ns/1.0">"#
+        ));
+        assert!(xml.contains(
+            r#"<link href="https://support.microsoft.com/help/5027231" rel="alternate" /><link href="https://msrc.microsoft.com/update-guide/vulnerability/CVE-2022-37967" rel="related" title="CVE-2022-37967" />"#
+        ));
+        assert!(
+            xml.contains(r#"<summary type="html">Body &amp;amp; &amp;lt;more&amp;gt;</summary>"#)
+        );
+        assert!(xml.contains(
+            r#"<category term="take-action" scheme="https://example.com/type" label="Take action" /><wmc:kb>5027231</wmc:kb><wmc:kb>5027215</wmc:kb><wmc:build>22621.1848</wmc:build></entry>"#
+        ));
+        let mut articles = articles();
+        articles[0].body = "<div>Windows&nbsp;11<br></div>".to_string();
+        let xml = atom(
+            "https://example.com/atom.xml",
+            articles,
+            "2023-06-14T00:00:00Z".to_string(),
+            ContentType::Xhtml,
+        )
+        .to_xml();
+        assert!(xml.contains(
+            r#"<content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><div>Windows&#160;11<br/></div></div></content>"#
+        ));
+    }
+    #[test]
+    fn render_rss() {
+        let xml = rss(
+            "https://example.com/rss.xml",
+            articles(),
+            "2023-06-14T00:00:00Z",
+        )
+        .to_xml();
+        assert!(xml.contains(r#"<atom:link href="https://example.com/rss.xml" rel="self" type="application/rss+xml" />"#));
+        assert!(xml.contains("<lastBuildDate>Wed, 14 Jun 2023 00:00:00 +0000</lastBuildDate>"));
+        assert!(xml.contains(
+            "<item><title>Take action: June 2023 security update &amp; more</title><link>https://support.microsoft.com/help/5027231</link><description>&lt;div&gt;body&lt;/div&gt;</description><guid isPermaLink=\"false\">https://learn.microsoft.com/en-us/windows/release-health/windows-message-center#3110</guid><pubDate>Tue, 13 Jun 2023 17:00:00 +0000</pubDate><category domain=\"https://example.com/type\">Take action</category></item>"
+        ));
+        let mut articles = articles();
+        articles[0].body.clear();
+        let xml = rss(
+            "https://example.com/rss.xml",
+            articles,
+            "2023-06-14T00:00:00Z",
+        )
+        .to_xml();
+        assert!(xml.contains("<description>Body &amp;amp; &amp;lt;more&amp;gt;</description>"));
+    }
+    #[test]
+    fn render_json_feed() {
+        let json = json_feed("https://example.com/feed.json", articles()).to_string();
+        assert!(json.starts_with(r#"{"version":"https://jsonfeed.org/version/1.1","#));
+        assert!(json.contains(r#""feed_url":"https://example.com/feed.json""#));
+        assert!(json.contains(r#""items":[{"id":"https://learn.microsoft.com/en-us/windows/release-health/windows-message-center#3110","url":"https://support.microsoft.com/help/5027231","title":"Take action: June 2023 security update & more","content_html":"\u003cdiv>body\u003c/div>","summary":"Body & \u003cmore>","date_published":"2023-06-13T17:00:00Z","tags":["take-action"],"_windows_message_center":{"kb":["5027231","5027215"],"builds":["22621.1848"],"versions":[],"cves":["CVE-2022-37967"]}}]"#));
+    }
+    #[test]
+    fn render_ics() {
+        let ics = ics(
+            Some("https://example.com/deadlines.ics"),
+            articles(),
+            "2023-06-14T00:00:00Z",
+        )
+        .to_ics();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains("SOURCE;VALUE=URI:https://example.com/deadlines.ics\r\n"));
+        assert!(ics.contains(concat!(
+            "BEGIN:VEVENT\r\n",
+            "UID:https://learn.microsoft.com/en-us/windows/release-health/windows-messag\r\n",
+            " e-center#3110/20230601\r\n",
+            "DTSTAMP:20230614T000000Z\r\n",
+            "DTSTART;VALUE=DATE:20230601\r\n",
+            "DTEND;VALUE=DATE:20230701\r\n",
+            "SUMMARY:Take action: June 2023 security update & more\r\n",
+            "DESCRIPTION:\"June 2023\" (medium confidence)\r\n",
+            "URL;VALUE=URI:https://support.microsoft.com/help/5027231\r\n",
+            "X-WMC-CONFIDENCE:medium\r\n",
+            "END:VEVENT\r\n",
+        )));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+}
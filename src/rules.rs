@@ -0,0 +1,343 @@
+use super::article::{Article, Category};
+use super::config::{self, Ini};
+use super::filter;
+use super::text;
+
+use regex::Regex;
+use std::cell::OnceCell;
+
+pub const RULE_SCHEME: &str =
+    "https://github.com/yumetodo/unofficial-windows-message-center-rss/categories/rule";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    // HTMLを取り除いたテキスト
+    Body,
+    Url,
+    // termとlabelのどちらか
+    Category,
+    Date,
+}
+
+#[derive(Debug)]
+enum Test {
+    // 大文字と小文字を区別しない部分一致
+    Contains(String),
+    Matches(Regex),
+    Before(String),
+    After(String),
+}
+impl Test {
+    fn test(&self, value: &str) -> bool {
+        match self {
+            Test::Contains(s) => text::normalize(value).to_lowercase().contains(s.as_str()),
+            Test::Matches(r) => r.is_match(value),
+            Test::Before(d) => value < d.as_str(),
+            Test::After(d) => value >= d.as_str(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Condition {
+    negate: bool,
+    field: Field,
+    test: Test,
+}
+impl Condition {
+    // [not] FIELD OP VALUE
+    fn parse(s: &str) -> Result<Self, String> {
+        let (negate, s) = match s.strip_prefix("not ") {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, s),
+        };
+        let mut it = s.splitn(3, char::is_whitespace);
+        let (Some(field), Some(op), Some(value)) = (it.next(), it.next(), it.next()) else {
+            return Err(format!("expected FIELD OP VALUE: {}", s));
+        };
+        let value = value.trim();
+        let field = match field {
+            "title" => Field::Title,
+            "body" => Field::Body,
+            "url" => Field::Url,
+            "category" => Field::Category,
+            "date" => Field::Date,
+            f => return Err(format!("unknown field: {}", f)),
+        };
+        let date = || filter::parse_since(value).ok_or_else(|| format!("invalid date: {}", value));
+        let test = match op {
+            "contains" => Test::Contains(text::normalize(value).to_lowercase()),
+            "matches" => Test::Matches(Regex::new(value).map_err(|e| e.to_string())?),
+            "before" if field == Field::Date => Test::Before(date()?),
+            "after" if field == Field::Date => Test::After(date()?),
+            o => return Err(format!("unknown operator for {}: {}", field_name(field), o)),
+        };
+        Ok(Condition {
+            negate,
+            field,
+            test,
+        })
+    }
+    fn test(&self, a: &Article, body: &OnceCell<String>) -> bool {
+        let matched = match self.field {
+            Field::Title => self.test.test(&a.title),
+            Field::Body => self.test.test(body.get_or_init(|| a.body_text())),
+            Field::Url => self.test.test(&a.url),
+            Field::Category => a
+                .categories
+                .iter()
+                .any(|c| self.test.test(&c.term) || self.test.test(&c.label)),
+            Field::Date => self.test.test(&a.date),
+        };
+        matched != self.negate
+    }
+}
+
+fn field_name(field: Field) -> &'static str {
+    match field {
+        Field::Title => "title",
+        Field::Body => "body",
+        Field::Url => "url",
+        Field::Category => "category",
+        Field::Date => "date",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Drop,
+    Tag(String),
+    Prefix(String),
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub name: String,
+    any: bool,
+    conditions: Vec<Condition>,
+    actions: Vec<Action>,
+}
+impl Rule {
+    fn matches(&self, a: &Article, body: &OnceCell<String>) -> bool {
+        if self.any {
+            self.conditions.iter().any(|c| c.test(a, body))
+        } else {
+            self.conditions.iter().all(|c| c.test(a, body))
+        }
+    }
+}
+
+// 上から順に当てはめる。dropされたお知らせには後のルールを当てはめない
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+impl Rules {
+    // 共通の設定ファイルの[rule NAME]のセクションごとに、if, match, actionを書く
+    pub fn from_config(ini: &Ini) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for (name, section) in ini.sections_of("rule") {
+            let mut rule = Rule {
+                name: name.to_string(),
+                any: false,
+                conditions: Vec::new(),
+                actions: Vec::new(),
+            };
+            for e in &section.entries {
+                let error = |message: &str| config::error(e.line, message).to_string();
+                let value = e.value.as_str();
+                match e.key.as_str() {
+                    "if" => rule
+                        .conditions
+                        .push(Condition::parse(value).map_err(|m| error(&m))?),
+                    "match" => {
+                        rule.any = match value {
+                            "all" => false,
+                            "any" => true,
+                            _ => return Err(error("match must be all or any")),
+                        }
+                    }
+                    "action" => {
+                        let (verb, arg) = value.split_once(' ').unwrap_or((value, ""));
+                        let arg = arg.trim();
+                        rule.actions.push(match (verb, arg) {
+                            ("drop", "") => Action::Drop,
+                            ("tag" | "prefix", "") => {
+                                return Err(error(&format!("{} needs a value", verb)))
+                            }
+                            ("tag", _) => Action::Tag(arg.to_string()),
+                            ("prefix", _) => Action::Prefix(arg.to_string()),
+                            _ => return Err(error(&format!("unknown action: {}", value))),
+                        });
+                    }
+                    k => return Err(error(&format!("unknown key: {}", k))),
+                }
+            }
+            if rule.conditions.is_empty() || rule.actions.is_empty() {
+                return Err(format!("rule [{}] needs an if and an action", rule.name));
+            }
+            rules.push(rule);
+        }
+        Ok(Rules { rules })
+    }
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+    // ルールごとに当てはまったお知らせの数を返す
+    pub fn apply(&self, articles: &mut Vec<Article>) -> Vec<usize> {
+        let mut counts = vec![0; self.rules.len()];
+        articles.retain_mut(|a| {
+            let body = OnceCell::new();
+            for (rule, count) in self.rules.iter().zip(counts.iter_mut()) {
+                if !rule.matches(a, &body) {
+                    continue;
+                }
+                *count += 1;
+                for action in &rule.actions {
+                    match action {
+                        Action::Drop => return false,
+                        Action::Tag(label) => {
+                            let term = text::slug(label);
+                            if !a
+                                .categories
+                                .iter()
+                                .any(|c| c.scheme == RULE_SCHEME && c.term == term)
+                            {
+                                a.categories.push(Category {
+                                    term,
+                                    scheme: RULE_SCHEME.to_string(),
+                                    label: label.clone(),
+                                });
+                            }
+                        }
+                        Action::Prefix(prefix) => a.title = format!("{} {}", prefix, a.title),
+                    }
+                }
+            }
+            true
+        });
+        counts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::classify::Classifier;
+    use crate::testing;
+    fn article(id: &str, title: &str, date: &str, body: &str) -> Article {
+        let mut a = Article {
+            url: format!("https://support.microsoft.com/help/{}", id),
+            title: title.to_string(),
+            date: date.to_string(),
+            ..testing::article(id, body)
+        };
+        a.categories = Classifier::default().classify(&a);
+        a
+    }
+    fn articles() -> Vec<Article> {
+        vec![
+            article(
+                "1",
+                "Reminder: Security hardening changes for Netlogon and Kerberos",
+                "2023-06-13T17:00:00Z",
+                "<div>Applies to Windows Server 2022.</div>",
+            ),
+            article(
+                "2",
+                "Take action: June 2023 security update is now available",
+                "2023-06-13T17:00:00Z",
+                "<div>The update for <b>Windows&nbsp;11</b> is available.</div>",
+            ),
+            article(
+                "3",
+                "Windows 10, version 21H2 end of servicing",
+                "2022-05-24T21:00:00Z",
+                "<div>Windows 10 Home and Pro.</div>",
+            ),
+        ]
+    }
+    fn parse(s: &str) -> Result<Rules, String> {
+        Rules::from_config(&Ini::parse(s)?)
+    }
+    const RULES: &str = "
+# 古いお知らせは出さない
+[rule old]
+if = date before 2023-01-01
+action = drop
+
+[rule kerberos]
+match = any
+if = title matches (?i)kerberos|netlogon
+if = body contains CVE-2022-37966
+action = tag Kerberos hardening
+action = prefix [AD]
+
+[rule client]
+if = body contains windows 11
+if = not url contains /help/1
+action = prefix [Client]
+";
+    #[test]
+    fn apply() {
+        let rules = parse(RULES).unwrap();
+        let mut articles = articles();
+        let counts = rules.apply(&mut articles);
+        assert_eq!(counts, [1, 1, 1]);
+        let names: Vec<&str> = rules.rules().iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["old", "kerberos", "client"]);
+        assert_eq!(articles.len(), 2);
+        assert_eq!(
+            articles[0].title,
+            "[AD] Reminder: Security hardening changes for Netlogon and Kerberos"
+        );
+        assert_eq!(
+            articles[0].categories.last(),
+            Some(&Category {
+                term: "kerberos-hardening".to_string(),
+                scheme: RULE_SCHEME.to_string(),
+                label: "Kerberos hardening".to_string(),
+            })
+        );
+        assert_eq!(
+            articles[1].title,
+            "[Client] Take action: June 2023 security update is now available"
+        );
+    }
+    #[test]
+    fn match_category() {
+        let rules =
+            parse("[rule reminders]\nif = category contains reminder\naction = drop").unwrap();
+        let mut articles = articles();
+        assert_eq!(rules.apply(&mut articles), [1]);
+        assert_eq!(articles.len(), 2);
+    }
+    #[test]
+    fn parse_errors() {
+        for s in [
+            "[rule r]\nif = title contains x",
+            "[rule r]\naction = drop",
+            "[rule r]\nif = author contains x\naction = drop",
+            "[rule r]\nif = title before 2023-01-01\naction = drop",
+            "[rule r]\nif = date after someday\naction = drop",
+            "[rule r]\nif = title matches (\naction = drop",
+            "[rule r]\nif = title contains\naction = drop",
+            "[rule r]\nif = title contains x\naction = tag",
+            "[rule r]\nif = title contains x\naction = hide",
+            "[rule r]\nif = title contains x\nmatch = one\naction = drop",
+            "[rule r]\nif = title contains x\naction = drop\ncolor = red",
+        ] {
+            assert!(parse(s).is_err(), "{}", s);
+        }
+        assert_eq!(
+            parse("[rule r]\nif = title contains x\naction = hide").unwrap_err(),
+            "line 3: unknown action: hide"
+        );
+        // 他の機能のセクションは読み飛ばす
+        assert_eq!(
+            parse("[webhook ops]\nkind = slack").unwrap().rules().len(),
+            0
+        );
+    }
+}
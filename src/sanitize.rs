@@ -0,0 +1,237 @@
+//! Sanitizing the HTML of the bodies.
+
+use super::config::{self, ParseError};
+
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+use std::collections::BTreeMap;
+
+// 要素名と、その要素で残す属性
+const DEFAULT_ALLOW_LIST: &str = "
+a href title
+b
+strong
+i
+em
+u
+s
+code
+pre
+br
+p
+div
+span
+ul
+ol start
+li
+blockquote
+h1
+h2
+h3
+h4
+h5
+h6
+table
+thead
+tbody
+tr
+th colspan rowspan
+td colspan rowspan
+img src alt title
+sup
+sub
+";
+
+// 中身ごと取り除く要素
+const DROP: [&str; 14] = [
+    "script", "style", "iframe", "object", "embed", "noscript", "template", "form", "input",
+    "button", "select", "textarea", "frame", "frameset",
+];
+const VOID: [&str; 3] = ["br", "img", "hr"];
+// 空でも表の形を保つために残す要素
+const KEEP_EMPTY: [&str; 2] = ["td", "th"];
+
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim().to_lowercase();
+    match url.split_once(':') {
+        // ':'より前に'/'や'?'や'#'があれば相対URL
+        Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => {
+            matches!(scheme, "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
+/// Rebuilds the HTML of a body from an allow-list of elements and their attributes.
+///
+/// Other elements are replaced by their content, except scripts, styles, frames and forms,
+/// which are removed with their content. Links only keep `http`, `https` and `mailto` URLs.
+#[derive(Debug)]
+pub struct Sanitizer {
+    allow: BTreeMap<String, Vec<String>>,
+}
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self::parse(DEFAULT_ALLOW_LIST).unwrap()
+    }
+}
+
+// 要素名と属性名に使える文字だけか
+fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'))
+}
+
+impl Sanitizer {
+    /// Reads one element per line, followed by the attributes it keeps, separated by
+    /// whitespace. Empty lines and lines starting with `#` are skipped.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut allow = BTreeMap::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| config::error(i + 1, &message);
+            let mut it = line.split_whitespace().map(str::to_lowercase);
+            let element = it.next().unwrap_or_default();
+            let attributes: Vec<String> = it.collect();
+            if let Some(name) = std::iter::once(&element)
+                .chain(&attributes)
+                .find(|s| !is_name(s))
+            {
+                return Err(error(format!("invalid name: {}", name)));
+            }
+            if DROP.contains(&element.as_str()) {
+                return Err(error(format!("{} is always removed", element)));
+            }
+            if allow.insert(element.clone(), attributes).is_some() {
+                return Err(error(format!("{} is listed twice", element)));
+            }
+        }
+        // 何も許可しなければ、すべての要素が取り除かれてしまう
+        if allow.is_empty() {
+            return Err(ParseError::new("no elements are allowed"));
+        }
+        Ok(Sanitizer { allow })
+    }
+    /// Returns the sanitized HTML of `body`.
+    pub fn sanitize(&self, body: &str) -> String {
+        let html = Html::parse_fragment(body);
+        let mut ret = String::with_capacity(body.len());
+        for child in html.root_element().children() {
+            ret += &self.node(child).0;
+        }
+        ret
+    }
+    // 変換後のHTMLと、空白以外のテキストや画像を含むかを返す
+    fn node(&self, node: NodeRef<Node>) -> (String, bool) {
+        let e = match node.value() {
+            Node::Text(t) => {
+                let has_content = !t.trim().is_empty();
+                return (String::from(html_escape::encode_text(&**t)), has_content);
+            }
+            Node::Element(e) => e,
+            _ => return (String::new(), false),
+        };
+        let name = e.name().to_lowercase();
+        if DROP.contains(&name.as_str()) {
+            return (String::new(), false);
+        }
+        let mut inner = String::new();
+        let mut has_content = name == "img";
+        for child in node.children() {
+            let (s, c) = self.node(child);
+            inner += &s;
+            has_content |= c;
+        }
+        let Some(attributes) = self.allow.get(&name) else {
+            // 許可されていない要素は中身だけ残す
+            return (inner, has_content);
+        };
+        let is_void = VOID.contains(&name.as_str());
+        if !is_void && !has_content && !KEEP_EMPTY.contains(&name.as_str()) {
+            return (String::new(), false);
+        }
+        let mut ret = format!("<{}", name);
+        for (key, value) in e.attrs() {
+            let key = key.to_lowercase();
+            if !attributes.contains(&key)
+                || key == "rel"
+                || (matches!(key.as_str(), "href" | "src") && !is_safe_url(value))
+            {
+                continue;
+            }
+            ret += &format!(
+                r#" {}="{}""#,
+                key,
+                html_escape::encode_double_quoted_attribute(value)
+            );
+        }
+        if name == "a" {
+            ret += r#" rel="noopener noreferrer""#;
+        }
+        ret += ">";
+        if !is_void {
+            ret += &inner;
+            ret += &format!("</{}>", name);
+        }
+        (ret, has_content)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn sanitize_default() {
+        let body = r#"<div class="x" style="color: red">See the&nbsp;<a
+    href="https://support.microsoft.com/help/5027231"
+    rel="noopener" target="_blank" data-linktype="external" aria-label="kb"
+    >Windows 11</a> <a href="javascript:alert(1)">page</a><script>alert(1)</script><iframe src="https://example.com"></iframe>.<font color="red"><b>Note</b></font><span>&nbsp;</span><img src="/a.png" width="10"><br></div><div><p> </p></div><table><tr><td></td><td>1 &lt; 2</td></tr></table>"#;
+        assert_eq!(
+            Sanitizer::default().sanitize(body),
+            "<div>See the\u{a0}<a href=\"https://support.microsoft.com/help/5027231\" rel=\"noopener noreferrer\">Windows 11</a> <a rel=\"noopener noreferrer\">page</a>.<b>Note</b><img src=\"/a.png\"><br></div><table><tbody><tr><td></td><td>1 &lt; 2</td></tr></tbody></table>"
+        );
+    }
+    #[test]
+    fn sanitize_configured() {
+        let s = Sanitizer::parse("# リンクと段落だけ\np\na href\n").unwrap();
+        assert_eq!(
+            s.sanitize(
+                r#"<div><p class="c">Hello <a href="/x" title="t"><u>world</u></a></p></div>"#
+            ),
+            r#"<p>Hello <a href="/x" rel="noopener noreferrer">world</a></p>"#
+        );
+    }
+    #[test]
+    fn parse_invalid() {
+        for s in [
+            "",
+            "# only a comment",
+            "a href=",
+            "a href,title",
+            "<p>",
+            "p\nscript",
+            "p\np class",
+        ] {
+            assert!(Sanitizer::parse(s).is_err(), "{}", s);
+        }
+        let e = Sanitizer::parse("p\na href,title").unwrap_err();
+        assert_eq!(e.line(), Some(2));
+        assert_eq!(e.message(), "invalid name: href,title");
+        assert_eq!(e.to_string(), "line 2: invalid name: href,title");
+        assert_eq!(Sanitizer::parse("").unwrap_err().line(), None);
+    }
+    #[test]
+    fn safe_url() {
+        assert!(is_safe_url("https://example.com"));
+        assert!(is_safe_url("/en-us/windows"));
+        assert!(is_safe_url("#3110"));
+        assert!(is_safe_url("mailto:a@example.com"));
+        assert!(is_safe_url("/search?q=a:b"));
+        assert!(!is_safe_url(" JavaScript:alert(1)"));
+        assert!(!is_safe_url("data:text/html,x"));
+    }
+}
@@ -0,0 +1,235 @@
+//! Versioned JSON and NDJSON representations of the announcements.
+//!
+//! A JSON document is `{"version":1,"articles":[...]}` and every line of NDJSON is one
+//! announcement with the `version` member added, so each stage of a pipeline can check
+//! that it understands its input.
+
+use super::article::Article;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The version written by this crate and the only one it reads.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// `value` with a `version` member added beside its own members.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Versioned<T> {
+    /// The schema version, [`SCHEMA_VERSION`] when written by this crate.
+    pub version: u32,
+    /// The versioned value.
+    #[serde(flatten)]
+    pub value: T,
+}
+impl<T> Versioned<T> {
+    /// Wraps `value` with the current [`SCHEMA_VERSION`].
+    pub fn new(value: T) -> Self {
+        Versioned {
+            version: SCHEMA_VERSION,
+            value,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document<A> {
+    articles: A,
+}
+
+// 本体を読む前にversionだけを確かめる
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// The error of [`from_json`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SchemaError {
+    /// The input is not JSON of the schema.
+    Json {
+        /// The line of NDJSON, or `None` for a JSON document.
+        line: Option<usize>,
+        /// The error of `serde_json`.
+        error: serde_json::Error,
+    },
+    /// The input has a `version` other than [`SCHEMA_VERSION`].
+    UnsupportedVersion(u32),
+}
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Json {
+                line: Some(line),
+                error,
+            } => write!(f, "line {}: {}", line, error),
+            SchemaError::Json { line: None, error } => write!(f, "{}", error),
+            SchemaError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported schema version {} (expected {})",
+                v, SCHEMA_VERSION
+            ),
+        }
+    }
+}
+impl std::error::Error for SchemaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SchemaError::Json { error, .. } => Some(error),
+            SchemaError::UnsupportedVersion(_) => None,
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(s: &str, line: Option<usize>) -> Result<T, SchemaError> {
+    let json = |error| SchemaError::Json { line, error };
+    let header: Header = serde_json::from_str(s).map_err(json)?;
+    if header.version != SCHEMA_VERSION {
+        return Err(SchemaError::UnsupportedVersion(header.version));
+    }
+    serde_json::from_str(s).map_err(json)
+}
+
+/// Writes the announcements as one JSON document.
+pub fn to_json(articles: &[Article]) -> String {
+    serde_json::to_string(&Versioned::new(Document { articles })).unwrap()
+}
+
+/// Writes the announcements as NDJSON, one announcement per line.
+pub fn to_ndjson(articles: &[Article]) -> String {
+    let mut ret = String::new();
+    for a in articles {
+        ret += &serde_json::to_string(&Versioned::new(a)).unwrap();
+        ret += "\n";
+    }
+    ret
+}
+
+/// Reads the output of [`to_json`] or [`to_ndjson`].
+pub fn from_json(s: &str) -> Result<Vec<Article>, SchemaError> {
+    // 1行だけのNDJSONもあるので、articlesの有無で見分ける
+    let is_document =
+        serde_json::from_str::<serde_json::Value>(s).is_ok_and(|v| v.get("articles").is_some());
+    if is_document {
+        let doc: Versioned<Document<Vec<Article>>> = parse(s, None)?;
+        return Ok(doc.value.articles);
+    }
+    let mut ret = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let a: Versioned<Article> = parse(line, Some(i + 1))?;
+        ret.push(a.value);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::article::Category;
+    use crate::feed::{Entry, Feed, Link, Person, XHTMLText};
+    use crate::testing;
+    fn articles() -> Vec<Article> {
+        let mut a = testing::article(
+            "3110",
+            "<div>The June 2023 security update\nis now available.</div>",
+        );
+        a.summary = "The June 2023 security update is now available.".to_string();
+        a.categories = vec![Category {
+            term: "windows-11".to_string(),
+            scheme: "https://example.com/product".to_string(),
+            label: "Windows 11".to_string(),
+        }];
+        a.kbs = vec!["5027231".to_string()];
+        let b = Article::new(
+            "3111".to_string(),
+            "https://example.com/atom.xml".to_string(),
+            "self",
+            "Reminder".to_string(),
+            "2023-06-12T17:00:00Z".to_string(),
+            "<div>Body</div>".to_string(),
+        );
+        vec![a, b]
+    }
+    #[test]
+    fn round_trip() {
+        let json = to_json(&articles());
+        assert!(json.starts_with(r#"{"version":1,"articles":[{"id":"3110","#));
+        assert_eq!(from_json(&json).unwrap(), articles());
+
+        let ndjson = to_ndjson(&articles());
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with(
+            r#"{"version":1,"id":"3111","url":"https://example.com/atom.xml","rel":"self","#
+        ));
+        assert_eq!(from_json(&ndjson).unwrap(), articles());
+        assert_eq!(from_json(lines[0]).unwrap(), articles()[..1]);
+    }
+    #[test]
+    fn read_errors() {
+        // 省略できるのはsummaryなどの派生した値だけ
+        let minimal = r#"{"version":1,"id":"1","url":"u","rel":"alternate","title":"t","date":"d","body":"b"}"#;
+        assert_eq!(from_json(minimal).unwrap()[0].summary, "");
+        assert!(matches!(
+            from_json(&minimal.replace(r#""version":1"#, r#""version":2"#)),
+            Err(SchemaError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            from_json(&minimal.replace("alternate", "related")),
+            Err(SchemaError::Json { line: Some(1), .. })
+        ));
+        assert!(matches!(
+            from_json(&format!("{}\n\n{{\"version\":1}}\n", minimal)),
+            Err(SchemaError::Json { line: Some(3), .. })
+        ));
+        assert!(from_json(r#"{"id":"1"}"#).is_err());
+    }
+    #[test]
+    fn feed_round_trip() {
+        let feed = Feed::new(
+            "urn:example".to_string(),
+            "Example".to_string(),
+            "2023-06-13T17:00:00Z".to_string(),
+        )
+        .author(vec![Person::new("Example").uri("https://example.com")])
+        .link(vec![Link::new()
+            .href("https://example.com/atom.xml")
+            .rel("self")
+            .type_("application/atom+xml")])
+        .entry(vec![Entry::new(
+            "urn:example:1".to_string(),
+            "A & B".into(),
+            "2023-06-13T17:00:00Z".to_string(),
+        )
+        .content(XHTMLText::new("<p>Body</p>"))]);
+        let json = serde_json::to_string(&Versioned::new(&feed)).unwrap();
+        assert!(json.contains(r#""type":"application/atom+xml""#));
+        assert!(json.contains(r#""title":"A & B""#));
+        assert!(json.contains(r#""content":{"xhtml":"<p>Body</p>"}"#));
+        let read: Versioned<Feed> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.version, SCHEMA_VERSION);
+        assert_eq!(read.value.to_xml(), feed.to_xml());
+    }
+    #[test]
+    fn escape_deserialized_feed() {
+        // JSONから読んだ値も、ビルダーで作ったときと同じようにエスケープする
+        let json = r#"{"id":"</id><x/>","title":"t","updated":"u","author":[{"name":"<x/>","uri":null,"email":null}],"link":[],"entry":[{"id":"1","title":"</title><x/>","updated":"u","author":[],"content":{"xhtml":"</div></content><x>"},"link":[{"href":"\"><x/>","rel":null,"type":null,"hreflang":null,"title":null}],"summary":"</summary><x/>","category":[],"extension":[{"name":"wmc:kb","value":"<x/>"}]}],"namespace":[]}"#;
+        let xml = serde_json::from_str::<Feed>(json).unwrap().to_xml();
+        assert!(!xml.contains("<x/>"), "{}", xml);
+        assert!(xml.contains(r#"<content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><x></x></div></content>"#));
+        let html: Entry =
+            serde_json::from_str(r#"{"id":"1","title":"t","updated":"u","author":[],"content":{"html":"</content><x/>"},"link":[],"summary":null,"category":[],"extension":[]}"#)
+                .unwrap();
+        let json = serde_json::to_string(&html).unwrap();
+        assert!(json.contains(r#""content":{"html":"</content><x/>"}"#));
+        let xml = Feed::new("1".into(), "t".into(), "u".into())
+            .entry(vec![html])
+            .to_xml();
+        assert!(xml.contains(r#"<content type="html">&lt;/content&gt;&lt;x/&gt;</content>"#));
+    }
+}
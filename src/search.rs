@@ -0,0 +1,377 @@
+use super::filter;
+use super::json::Json;
+
+// 抜粋の中で一致した語を囲む印。表示するときに出力形式に合わせて置き換える
+pub const HIGHLIGHT_START: char = '\u{E000}';
+pub const HIGHLIGHT_END: char = '\u{E001}';
+
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Sort {
+    #[default]
+    Relevance,
+    // 新しい順
+    Date,
+}
+impl Sort {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "relevance" => Some(Sort::Relevance),
+            "date" => Some(Sort::Date),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    // FTS5のMATCHに渡す式
+    pub expr: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub categories: Vec<String>,
+    pub sort: Sort,
+    pub limit: usize,
+}
+impl Query {
+    pub fn new(query: &str) -> Result<Self, String> {
+        Ok(Query {
+            expr: to_fts(query)?,
+            since: None,
+            until: None,
+            categories: Vec::new(),
+            sort: Sort::default(),
+            limit: 20,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub id: String,
+    pub title: String,
+    pub date: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+// YYYY-MM-DDならその日の終わりまでを含める
+pub fn parse_until(s: &str) -> Option<String> {
+    let until = filter::parse_since(s)?;
+    Some(match until.strip_suffix("T00:00:00Z") {
+        Some(day) if s.len() == 10 => format!("{}T23:59:59Z", day),
+        _ => until,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Term(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut ret = Vec::new();
+    let mut it = s.chars().peekable();
+    while let Some(&c) = it.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                it.next();
+            }
+            '(' | ')' => {
+                it.next();
+                ret.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '"' => {
+                it.next();
+                let mut phrase = String::new();
+                loop {
+                    match it.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err("unterminated phrase".to_string()),
+                    }
+                }
+                ret.push(Token::Phrase(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = it.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    it.next();
+                }
+                match word.as_str() {
+                    "AND" => ret.push(Token::And),
+                    "OR" => ret.push(Token::Or),
+                    "NOT" => ret.push(Token::Not),
+                    // -wordはNOT wordと同じ。-"..."と-(...)も書ける
+                    "-" if matches!(it.peek(), Some('"' | '(')) => ret.push(Token::Not),
+                    _ => match word.strip_prefix('-') {
+                        Some(w) if !w.is_empty() => {
+                            ret.push(Token::Not);
+                            ret.push(Token::Term(w.to_string()));
+                        }
+                        _ => ret.push(Token::Term(word)),
+                    },
+                }
+            }
+        }
+    }
+    Ok(ret)
+}
+
+// 語はすべて""で囲み、FTS5の構文として解釈されないようにする
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+// 検索式をFTS5の式に変換する
+// 語を並べるとAND、"..."は語順どおりの一致、末尾の*は前方一致
+pub fn to_fts(s: &str) -> Result<String, String> {
+    let tokens = tokenize(s)?;
+    if tokens.is_empty() {
+        return Err("empty query".to_string());
+    }
+    let mut it = tokens.into_iter().peekable();
+    let expr = or(&mut it)?;
+    match it.next() {
+        None => Ok(expr),
+        Some(Token::Close) => Err("unbalanced ')'".to_string()),
+        Some(_) => Err("unexpected operator".to_string()),
+    }
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<Token>>;
+
+fn or(it: &mut Tokens) -> Result<String, String> {
+    let mut groups = vec![and(it)?];
+    while it.next_if_eq(&Token::Or).is_some() {
+        groups.push(and(it)?);
+    }
+    if groups.len() == 1 {
+        return Ok(groups.pop().unwrap().0);
+    }
+    Ok(groups
+        .into_iter()
+        .map(|(g, compound)| if compound { format!("({})", g) } else { g })
+        .collect::<Vec<_>>()
+        .join(" OR "))
+}
+
+// ORで区切られた1つのまとまり。組み立てた式と、括弧が必要かを返す
+// FTS5では暗黙のANDがNOTより強く結びつくので、除外する語は最後にまとめる
+fn and(it: &mut Tokens) -> Result<(String, bool), String> {
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+    loop {
+        match it.peek() {
+            Some(Token::And) => {
+                it.next();
+                if include.is_empty() && exclude.is_empty()
+                    || !matches!(
+                        it.peek(),
+                        Some(Token::Term(_) | Token::Phrase(_) | Token::Open | Token::Not)
+                    )
+                {
+                    return Err("AND and OR need a term on both sides".to_string());
+                }
+            }
+            Some(Token::Not) => {
+                it.next();
+                exclude.push(operand(it)?);
+            }
+            Some(Token::Term(_) | Token::Phrase(_) | Token::Open) => include.push(operand(it)?),
+            Some(Token::Or | Token::Close) | None => break,
+        }
+    }
+    if include.is_empty() {
+        return Err(if exclude.is_empty() {
+            "AND and OR need a term on both sides".to_string()
+        } else {
+            // FTS5のNOTは2項演算子なので、何かを除外する形でしか書けない
+            "NOT needs a term to exclude from".to_string()
+        });
+    }
+    let compound = include.len() > 1 || !exclude.is_empty();
+    let mut ret = include.join(" ");
+    if include.len() > 1 && !exclude.is_empty() {
+        ret = format!("({})", ret);
+    }
+    for e in exclude {
+        ret += " NOT ";
+        ret += &e;
+    }
+    Ok((ret, compound))
+}
+
+fn operand(it: &mut Tokens) -> Result<String, String> {
+    match it.next() {
+        Some(Token::Term(t)) => Ok(match t.strip_suffix('*') {
+            Some(prefix) if !prefix.is_empty() => quote(prefix) + "*",
+            _ => quote(&t),
+        }),
+        Some(Token::Phrase(p)) if p.trim().is_empty() => Err("empty phrase".to_string()),
+        Some(Token::Phrase(p)) => Ok(quote(&p)),
+        Some(Token::Open) => {
+            let expr = or(it)?;
+            match it.next() {
+                Some(Token::Close) => Ok(format!("({})", expr)),
+                _ => Err("unbalanced '('".to_string()),
+            }
+        }
+        _ => Err("NOT needs a term after it".to_string()),
+    }
+}
+
+fn highlight(snippet: &str, start: &str, end: &str) -> String {
+    snippet
+        .replace(HIGHLIGHT_START, start)
+        .replace(HIGHLIGHT_END, end)
+}
+
+pub fn text(hits: &[Hit], color: bool) -> String {
+    let mut ret = String::new();
+    for h in hits {
+        let (start, end) = if color { (BOLD, RESET) } else { ("*", "*") };
+        ret += &format!(
+            "{}\t{}\t{}\n  {}\n",
+            h.id,
+            h.date,
+            h.title,
+            highlight(&h.snippet, start, end)
+        );
+    }
+    ret
+}
+
+// 抜粋はHTMLとして扱えるように逃がし、一致した語を<mark>で囲む
+pub fn json(hits: &[Hit]) -> Json {
+    Json::Array(
+        hits.iter()
+            .map(|h| {
+                Json::object(vec![
+                    ("id", h.id.as_str().into()),
+                    ("title", h.title.as_str().into()),
+                    ("date", h.date.as_str().into()),
+                    ("url", h.url.as_str().into()),
+                    (
+                        "snippet",
+                        highlight(&html_escape::encode_text(&h.snippet), "<mark>", "</mark>")
+                            .into(),
+                    ),
+                ])
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn translate_query() {
+        assert_eq!(to_fts("Kerberos").unwrap(), r#""Kerberos""#);
+        assert_eq!(
+            to_fts(r#"Kerberos "security update" kerb*"#).unwrap(),
+            r#""Kerberos" "security update" "kerb"*"#
+        );
+        assert_eq!(
+            to_fts("(Kerberos OR Netlogon) AND NOT CVE-2022-37966").unwrap(),
+            r#"("Kerberos" OR "Netlogon") NOT "CVE-2022-37966""#
+        );
+        // 除外する語は後ろに回す
+        assert_eq!(
+            to_fts("Kerberos -Netlogon Windows -Server").unwrap(),
+            r#"("Kerberos" "Windows") NOT "Netlogon" NOT "Server""#
+        );
+        assert_eq!(
+            to_fts("Kerberos -Netlogon OR update").unwrap(),
+            r#"("Kerberos" NOT "Netlogon") OR "update""#
+        );
+        assert_eq!(
+            to_fts(r#"update -"Windows 10" -(Kerberos OR Netlogon)"#).unwrap(),
+            r#""update" NOT "Windows 10" NOT ("Kerberos" OR "Netlogon")"#
+        );
+        for q in [
+            "",
+            "NOT Kerberos",
+            "Kerberos OR NOT Netlogon",
+            "Kerberos NOT",
+            "Kerberos AND AND Netlogon",
+            "Kerberos OR",
+            "AND Kerberos",
+            "(Kerberos",
+            "Kerberos)",
+            "()",
+            "\"unterminated",
+            "\"\"",
+        ] {
+            assert!(to_fts(q).is_err(), "{}", q);
+        }
+    }
+    #[test]
+    fn exclude_with_fts5() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE t USING fts5(body);
+             INSERT INTO t (rowid, body) VALUES
+               (1, 'kerberos netlogon'),
+               (2, 'kerberos windows'),
+               (3, 'kerberos netlogon windows'),
+               (4, 'netlogon windows');",
+        )
+        .unwrap();
+        let rows = |q: &str| -> Vec<i64> {
+            let mut stmt = conn
+                .prepare("SELECT rowid FROM t WHERE t MATCH ? ORDER BY rowid")
+                .unwrap();
+            stmt.query_map([to_fts(q).unwrap()], |r| r.get(0))
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        assert_eq!(rows("kerberos -netlogon windows"), [2]);
+        assert_eq!(rows("kerberos AND NOT netlogon AND windows"), [2]);
+        assert_eq!(rows("windows -kerberos -netlogon"), Vec::<i64>::new());
+        assert_eq!(rows("kerberos -windows OR netlogon -kerberos"), [1, 4]);
+        assert_eq!(rows("(kerberos OR netlogon) -windows"), [1]);
+    }
+    #[test]
+    fn until() {
+        assert_eq!(parse_until("2022-12-31").unwrap(), "2022-12-31T23:59:59Z");
+        assert_eq!(
+            parse_until("2022-12-31T00:00:00+09:00").unwrap(),
+            "2022-12-30T15:00:00Z"
+        );
+        assert!(parse_until("someday").is_none());
+    }
+    #[test]
+    fn render() {
+        let hits = [Hit {
+            id: "3113".to_string(),
+            title: "Reminder".to_string(),
+            date: "2023-06-13T17:00:00Z".to_string(),
+            url: "https://support.microsoft.com/help/5021131".to_string(),
+            snippet: "…changes for \u{E000}Kerberos\u{E001} <PAC>…".to_string(),
+        }];
+        assert_eq!(
+            text(&hits, false),
+            "3113\t2023-06-13T17:00:00Z\tReminder\n  …changes for *Kerberos* <PAC>…\n"
+        );
+        assert!(text(&hits, true).contains("\x1b[1mKerberos\x1b[0m"));
+        assert_eq!(
+            json(&hits).to_string(),
+            r#"[{"id":"3113","title":"Reminder","date":"2023-06-13T17:00:00Z","url":"https://support.microsoft.com/help/5021131","snippet":"…changes for \u003cmark>Kerberos\u003c/mark> &lt;PAC&gt;…"}]"#
+        );
+    }
+}
@@ -0,0 +1,512 @@
+use super::article::Article;
+use super::cli::Source;
+use super::filter::Filter;
+use super::hash::fnv1a64;
+use super::json::Json;
+use super::parser::Parser;
+use super::render::{self, FeedOptions};
+use super::rules::Rules;
+use super::state;
+
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    articles: Vec<Article>,
+    fingerprint: u64,
+    last_modified: Option<DateTime<Utc>>,
+    last_success: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+impl Snapshot {
+    pub fn update(&mut self, articles: Vec<Article>, at: DateTime<Utc>) {
+        let fingerprints: Vec<String> = articles.iter().map(state::fingerprint).collect();
+        let fingerprint = fnv1a64(fingerprints.join("").as_bytes());
+        if self.last_modified.is_none() || fingerprint != self.fingerprint {
+            self.last_modified = Some(at);
+            self.fingerprint = fingerprint;
+        }
+        self.articles = articles;
+        self.last_success = Some(at);
+        self.last_error = None;
+    }
+    pub fn fail(&mut self, error: String) {
+        self.last_error = Some(error);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Atom,
+    Rss,
+    Json,
+    Ics,
+}
+impl Format {
+    fn from_path(path: &str) -> Option<Self> {
+        match path {
+            "/atom.xml" => Some(Format::Atom),
+            "/rss.xml" => Some(Format::Rss),
+            "/feed.json" => Some(Format::Json),
+            "/deadlines.ics" => Some(Format::Ics),
+            _ => None,
+        }
+    }
+    fn path(&self) -> &'static str {
+        match self {
+            Format::Atom => "/atom.xml",
+            Format::Rss => "/rss.xml",
+            Format::Json => "/feed.json",
+            Format::Ics => "/deadlines.ics",
+        }
+    }
+    fn content_type(&self) -> &'static str {
+        match self {
+            Format::Atom => "application/atom+xml; charset=utf-8",
+            Format::Rss => "application/rss+xml; charset=utf-8",
+            Format::Json => "application/feed+json; charset=utf-8",
+            Format::Ics => "text/calendar; charset=utf-8",
+        }
+    }
+}
+
+fn http_date(d: &DateTime<Utc>) -> String {
+    d.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn iso8601(d: &DateTime<Utc>) -> String {
+    d.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("header names and values are ASCII")
+}
+
+fn request_header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+fn not_modified(request: &Request, etag: &str, last_modified: &DateTime<Utc>) -> bool {
+    // If-None-Matchがある場合はIf-Modified-Sinceを見ない (RFC 9110 13.1.3)
+    if let Some(inm) = request_header(request, "If-None-Match") {
+        return inm
+            .split(',')
+            .map(|t| t.trim().trim_start_matches("W/"))
+            .any(|t| t == "*" || t == etag);
+    }
+    request_header(request, "If-Modified-Since")
+        .and_then(|s| DateTime::parse_from_rfc2822(s).ok())
+        .is_some_and(|since| last_modified.timestamp() <= since.timestamp())
+}
+
+fn render(
+    format: Format,
+    base_url: &str,
+    options: FeedOptions,
+    filter: &Filter,
+    snapshot: &Snapshot,
+    updated: &DateTime<Utc>,
+) -> String {
+    let query = filter.canonical_query();
+    let mut self_uri = format!("{}{}", base_url, format.path());
+    if !query.is_empty() {
+        self_uri += "?";
+        self_uri += &query;
+    }
+    let articles = filter.apply(snapshot.articles.clone());
+    // 予定表はお知らせごとの期限が必要なので、まとめない
+    let articles = match format {
+        Format::Ics => articles,
+        _ => options.group(articles),
+    };
+    match format {
+        Format::Atom => render::atom_with_id(
+            render::filtered_feed_id(&query),
+            &self_uri,
+            articles,
+            iso8601(updated),
+            options.content_type,
+        )
+        .to_xml(),
+        Format::Rss => render::rss(&self_uri, articles, &iso8601(updated)).to_xml(),
+        Format::Json => render::json_feed(&self_uri, articles).to_string(),
+        Format::Ics => render::ics(Some(&self_uri), articles, &iso8601(updated)).to_ics(),
+    }
+}
+
+fn healthz(snapshot: &Snapshot) -> Response<std::io::Cursor<Vec<u8>>> {
+    let body = Json::object(vec![
+        (
+            "status",
+            if snapshot.last_success.is_some() {
+                "ok"
+            } else {
+                "unavailable"
+            }
+            .into(),
+        ),
+        (
+            "last_success",
+            snapshot.last_success.as_ref().map(iso8601).into(),
+        ),
+        ("last_error", snapshot.last_error.clone().into()),
+        ("parse_count", snapshot.articles.len().into()),
+    ]);
+    Response::from_string(body.to_string())
+        .with_status_code(if snapshot.last_success.is_some() {
+            200
+        } else {
+            503
+        })
+        .with_header(header("Content-Type", "application/json; charset=utf-8"))
+        .with_header(header("Cache-Control", "no-store"))
+}
+
+fn handle(
+    request: Request,
+    base_url: &str,
+    options: FeedOptions,
+    max_age: Duration,
+    snapshot: &RwLock<Snapshot>,
+) {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let (path, query) = (path.to_string(), query.to_string());
+    let response = if !matches!(request.method(), Method::Get | Method::Head) {
+        Response::from_string("method not allowed")
+            .with_status_code(405)
+            .with_header(header("Allow", "GET, HEAD"))
+    } else if path == "/healthz" {
+        healthz(&snapshot.read().unwrap())
+    } else if let Some(format) = Format::from_path(&path) {
+        let snapshot = snapshot.read().unwrap();
+        match (snapshot.last_modified, Filter::from_query(&query)) {
+            (_, Err(e)) => Response::from_string(e).with_status_code(400),
+            (None, _) => Response::from_string("feed is not available yet")
+                .with_status_code(503)
+                .with_header(header("Retry-After", "60")),
+            (Some(last_modified), Ok(filter)) => {
+                let body = render(
+                    format,
+                    base_url,
+                    options,
+                    &filter,
+                    &snapshot,
+                    &last_modified,
+                );
+                let etag = format!("\"{:016x}\"", fnv1a64(body.as_bytes()));
+                let response = if not_modified(&request, &etag, &last_modified) {
+                    Response::from_string("").with_status_code(304)
+                } else {
+                    Response::from_string(body)
+                        .with_header(header("Content-Type", format.content_type()))
+                };
+                response
+                    .with_header(header("ETag", &etag))
+                    .with_header(header("Last-Modified", &http_date(&last_modified)))
+                    .with_header(header(
+                        "Cache-Control",
+                        &format!("public, max-age={}", max_age.as_secs()),
+                    ))
+            }
+        }
+    } else {
+        Response::from_string("not found").with_status_code(404)
+    };
+    if let Err(e) = request.respond(response) {
+        eprintln!("failed to respond to {}: {}", path, e);
+    }
+}
+
+pub fn refresh(source: &Source, parser: &Parser, rules: &Rules, snapshot: &RwLock<Snapshot>) {
+    match source.read_html() {
+        Ok(doc) => {
+            let mut articles = parser.parse(&doc);
+            rules.apply(&mut articles);
+            snapshot.write().unwrap().update(articles, Utc::now());
+        }
+        Err(e) => {
+            eprintln!("refresh failed: {}", e);
+            snapshot.write().unwrap().fail(e.to_string());
+        }
+    }
+}
+
+fn serve(
+    server: Server,
+    base_url: &str,
+    options: FeedOptions,
+    max_age: Duration,
+    snapshot: &RwLock<Snapshot>,
+) {
+    for request in server.incoming_requests() {
+        handle(request, base_url, options, max_age, snapshot);
+    }
+}
+
+// new_parserはAtomフィードのURLを受け取ってParserを作る
+pub fn run<F: FnOnce(&str) -> Parser>(
+    listen: &str,
+    interval: Duration,
+    source: Source,
+    options: FeedOptions,
+    rules: Rules,
+    new_parser: F,
+) -> Result<(), String> {
+    let server = Server::http(listen).map_err(|e| format!("{}: {}", listen, e))?;
+    let base_url = source.self_uri.trim_end_matches('/').to_string();
+    let parser = new_parser(&format!("{}{}", base_url, Format::Atom.path()));
+    let snapshot = Arc::new(RwLock::new(Snapshot::default()));
+    refresh(&source, &parser, &rules, &snapshot);
+    {
+        let snapshot = snapshot.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            refresh(&source, &parser, &rules, &snapshot);
+        });
+    }
+    serve(server, &base_url, options, interval, &snapshot);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::classify::Classifier;
+    use crate::config::Ini;
+    use crate::fetch::FetchMode;
+    use crate::grouping::{Grouping, Period};
+    use crate::testing;
+    use reqwest::blocking::Client;
+    use reqwest::StatusCode;
+
+    fn article(id: &str) -> Article {
+        testing::article(id, "<div>body</div>")
+    }
+    fn start(snapshot: Snapshot, options: FeedOptions) -> String {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", server.server_addr());
+        let snapshot = Arc::new(RwLock::new(snapshot));
+        {
+            let base_url = base_url.clone();
+            thread::spawn(move || {
+                serve(
+                    server,
+                    &base_url,
+                    options,
+                    Duration::from_secs(600),
+                    &snapshot,
+                )
+            });
+        }
+        base_url
+    }
+    fn client() -> Client {
+        Client::builder().no_proxy().build().unwrap()
+    }
+    #[test]
+    fn serve_feeds() {
+        let mut snapshot = Snapshot::default();
+        let at = DateTime::parse_from_rfc3339("2023-06-14T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        snapshot.update(vec![article("3110")], at);
+        let base_url = start(snapshot, FeedOptions::default());
+        let client = client();
+        for (path, content_type, needle) in [
+            ("/atom.xml", "application/atom+xml; charset=utf-8", "<feed"),
+            ("/rss.xml", "application/rss+xml; charset=utf-8", "<rss"),
+            (
+                "/feed.json",
+                "application/feed+json; charset=utf-8",
+                "jsonfeed",
+            ),
+            (
+                "/deadlines.ics",
+                "text/calendar; charset=utf-8",
+                "BEGIN:VCALENDAR",
+            ),
+        ] {
+            let res = client.get(format!("{}{}", base_url, path)).send().unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            let headers = res.headers().clone();
+            assert_eq!(headers["content-type"], content_type);
+            assert_eq!(headers["last-modified"], "Wed, 14 Jun 2023 00:00:00 GMT");
+            assert_eq!(headers["cache-control"], "public, max-age=600");
+            let body = res.text().unwrap();
+            assert!(body.contains(needle));
+            assert!(body.contains(&format!("{}{}", base_url, path)));
+
+            let etag = headers["etag"].to_str().unwrap();
+            let res = client
+                .get(format!("{}{}", base_url, path))
+                .header("If-None-Match", etag)
+                .send()
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+            let res = client
+                .get(format!("{}{}", base_url, path))
+                .header("If-Modified-Since", "Wed, 14 Jun 2023 00:00:00 GMT")
+                .send()
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+            let res = client
+                .get(format!("{}{}", base_url, path))
+                .header("If-Modified-Since", "Tue, 13 Jun 2023 00:00:00 GMT")
+                .send()
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+        let res = client.get(format!("{}/healthz", base_url)).send().unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.text().unwrap(),
+            r#"{"status":"ok","last_success":"2023-06-14T00:00:00Z","last_error":null,"parse_count":1}"#
+        );
+        let res = client.get(format!("{}/unknown", base_url)).send().unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = client
+            .post(format!("{}/atom.xml", base_url))
+            .send()
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+    #[test]
+    fn serve_filtered_feeds() {
+        let mut snapshot = Snapshot::default();
+        let mut reminder = article("3113");
+        reminder.title = "Reminder: Security hardening changes for Netlogon and Kerberos".into();
+        reminder.categories = Classifier::default().classify(&reminder);
+        snapshot.update(vec![article("3110"), reminder], Utc::now());
+        let base_url = start(snapshot, FeedOptions::default());
+        let client = client();
+        let body = client
+            .get(format!(
+                "{}/atom.xml?limit=10&category=reminder&q=Kerberos",
+                base_url
+            ))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert!(body.contains(
+            "<id>https://github.com/yumetodo/unofficial-windows-message-center-rss?q=kerberos&amp;category=reminder&amp;limit=10</id>"
+        ));
+        assert!(body.contains(&format!(
+            r#"<link href="{}/atom.xml?q=kerberos&amp;category=reminder&amp;limit=10" rel="self""#,
+            base_url
+        )));
+        assert!(body.contains("#3113</id>"));
+        assert!(!body.contains("#3110</id>"));
+        let res = client
+            .get(format!("{}/feed.json?since=someday", base_url))
+            .send()
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+    #[test]
+    fn serve_grouped_feeds() {
+        let mut snapshot = Snapshot::default();
+        snapshot.update(vec![article("3110"), article("3111")], Utc::now());
+        let base_url = start(
+            snapshot,
+            FeedOptions {
+                grouping: Some(Grouping::new(Period::Day, chrono_tz::UTC)),
+                ..Default::default()
+            },
+        );
+        let client = client();
+        let get = |path: &str| {
+            client
+                .get(format!("{}{}", base_url, path))
+                .send()
+                .unwrap()
+                .text()
+                .unwrap()
+        };
+        let body = get("/atom.xml");
+        assert!(body.contains("#day-2023-06-13</id>"));
+        assert!(!body.contains("#3110</id>"));
+        assert!(get("/feed.json").contains("Announcements of 2023-06-13 (2)"));
+        // 予定表はまとめない
+        assert!(!get("/deadlines.ics").contains("day-2023-06-13"));
+    }
+    #[test]
+    fn unavailable_before_first_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = Source {
+            self_uri: "http://localhost".to_string(),
+            path: Some(dir.path().join("missing.html").display().to_string()),
+            fetch_mode: FetchMode::Web,
+            warc: None,
+        };
+        let snapshot = RwLock::new(Snapshot::default());
+        refresh(&source, &Parser::new("", ""), &Rules::default(), &snapshot);
+        let base_url = start(snapshot.into_inner().unwrap(), FeedOptions::default());
+        let client = client();
+        let res = client.get(format!("{}/atom.xml", base_url)).send().unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let res = client.get(format!("{}/healthz", base_url)).send().unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(res.text().unwrap().contains(r#""last_error":"io error: "#));
+    }
+    #[test]
+    fn refresh_applies_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("page.html");
+        let row = |id: &str, title: &str| {
+            format!(
+                r##"<tr><td id="{id}"><b>{title}</b><a href="#{id}" data-linktype="self-bookmark"></a><div>Body</div></td><td>2023-06-13 <br>10:00 PT</td></tr>"##
+            )
+        };
+        std::fs::write(
+            &path,
+            format!(
+                r#"<h2 id="recent-announcements">Recent announcements</h2><table>{}{}</table>"#,
+                row("3110", "Take action: June 2023 security update"),
+                row("3111", "Windows 10 Home end of servicing"),
+            ),
+        )
+        .unwrap();
+        let source = Source {
+            self_uri: "http://localhost".to_string(),
+            path: Some(path.display().to_string()),
+            fetch_mode: FetchMode::Web,
+            warc: None,
+        };
+        let rules = Rules::from_config(
+            &Ini::parse("[rule home]\nif = title contains Home\naction = drop").unwrap(),
+        )
+        .unwrap();
+        let snapshot = RwLock::new(Snapshot::default());
+        refresh(&source, &Parser::new("", ""), &rules, &snapshot);
+        let ids: Vec<String> = snapshot
+            .read()
+            .unwrap()
+            .articles
+            .iter()
+            .map(|a| a.id.clone())
+            .collect();
+        assert_eq!(ids, ["3110"]);
+    }
+    #[test]
+    fn last_modified_changes_only_with_content() {
+        let mut snapshot = Snapshot::default();
+        let t1 = Utc::now();
+        let t2 = t1 + chrono::Duration::hours(1);
+        let t3 = t2 + chrono::Duration::hours(1);
+        snapshot.update(vec![article("1")], t1);
+        snapshot.update(vec![article("1")], t2);
+        assert_eq!(snapshot.last_modified, Some(t1));
+        assert_eq!(snapshot.last_success, Some(t2));
+        snapshot.update(vec![article("1"), article("2")], t3);
+        assert_eq!(snapshot.last_modified, Some(t3));
+    }
+}
@@ -0,0 +1,114 @@
+use super::article::Article;
+use super::hash::fnv1a64;
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archived {
+    pub article: Article,
+    pub first_seen: String,
+    pub last_changed: String,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    New,
+    Changed,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct State {
+    pub articles: BTreeMap<String, Archived>,
+    // 通知先の名前とお知らせのidごとに、最後に通知したときのfingerprint
+    pub sent: BTreeMap<(String, String), String>,
+    // ダイジェストなどが、どの時点までのお知らせを処理したか
+    pub watermarks: BTreeMap<String, String>,
+    // mergeで見つかった版。保存するまでに何度も変わったときも、途中の版を残せる
+    pub revisions: Vec<Archived>,
+}
+
+pub fn fingerprint(a: &Article) -> String {
+    let s = [a.title.as_str(), a.url.as_str(), a.rel, a.body.as_str()].join("\0");
+    format!("{:016x}", fnv1a64(s.as_bytes()))
+}
+
+impl State {
+    pub fn merge(&mut self, articles: &[Article], seen_at: &str) -> Vec<(String, Change)> {
+        let mut changes = Vec::new();
+        for a in articles {
+            let fingerprint = fingerprint(a);
+            match self.articles.get_mut(&a.id) {
+                // 本文が同じでも、分類などの設定が変わったときのために派生した値は入れ替える
+                Some(archived) if archived.fingerprint == fingerprint => {
+                    archived.article = a.clone()
+                }
+                Some(archived) => {
+                    archived.article = a.clone();
+                    archived.last_changed = seen_at.to_string();
+                    archived.fingerprint = fingerprint;
+                    self.revisions.push(archived.clone());
+                    changes.push((a.id.clone(), Change::Changed));
+                }
+                None => {
+                    let archived = Archived {
+                        article: a.clone(),
+                        first_seen: seen_at.to_string(),
+                        last_changed: seen_at.to_string(),
+                        fingerprint,
+                    };
+                    self.revisions.push(archived.clone());
+                    self.articles.insert(a.id.clone(), archived);
+                    changes.push((a.id.clone(), Change::New));
+                }
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::article;
+    #[test]
+    fn merge() {
+        let mut state = State::default();
+        let changes = state.merge(
+            &[article("1", "a"), article("2", "b")],
+            "2023-06-13T00:00:00Z",
+        );
+        assert_eq!(
+            changes,
+            [
+                ("1".to_string(), Change::New),
+                ("2".to_string(), Change::New)
+            ]
+        );
+        let changes = state.merge(
+            &[article("1", "a"), article("2", "c")],
+            "2023-06-14T00:00:00Z",
+        );
+        assert_eq!(changes, [("2".to_string(), Change::Changed)]);
+        let archived = &state.articles["2"];
+        assert_eq!(archived.first_seen, "2023-06-13T00:00:00Z");
+        assert_eq!(archived.last_changed, "2023-06-14T00:00:00Z");
+        assert_eq!(archived.article.body, "c");
+        assert_eq!(state.articles["1"].last_changed, "2023-06-13T00:00:00Z");
+        let revisions: Vec<(&str, &str)> = state
+            .revisions
+            .iter()
+            .map(|a| (a.article.id.as_str(), a.article.body.as_str()))
+            .collect();
+        assert_eq!(revisions, [("1", "a"), ("2", "b"), ("2", "c")]);
+        // 本文が同じなら版は増やさず、要約などだけを入れ替える
+        let mut reclassified = article("1", "a");
+        reclassified.summary = "new summary".to_string();
+        assert!(state
+            .merge(&[reclassified], "2023-06-15T00:00:00Z")
+            .is_empty());
+        assert_eq!(state.articles["1"].article.summary, "new summary");
+        assert_eq!(state.articles["1"].last_changed, "2023-06-13T00:00:00Z");
+        assert_eq!(state.revisions.len(), 3);
+    }
+}
@@ -0,0 +1,561 @@
+use super::article::{self, Article, Category};
+use super::search::{Hit, Query, Sort, HIGHLIGHT_END, HIGHLIGHT_START};
+use super::state::{Archived, State};
+
+use rusqlite::{params, params_from_iter, Connection, Transaction};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+// i番目の要素でuser_versionをiからi + 1に上げる。適用済みのものは変更しない
+const MIGRATIONS: [&str; 1] = ["
+CREATE TABLE articles (
+    id TEXT PRIMARY KEY,
+    url TEXT NOT NULL,
+    rel TEXT NOT NULL,
+    title TEXT NOT NULL,
+    date TEXT NOT NULL,
+    body TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    first_seen TEXT NOT NULL,
+    last_changed TEXT NOT NULL,
+    fingerprint TEXT NOT NULL
+);
+CREATE TABLE categories (
+    article_id TEXT NOT NULL REFERENCES articles(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    term TEXT NOT NULL,
+    scheme TEXT NOT NULL,
+    label TEXT NOT NULL,
+    PRIMARY KEY (article_id, position)
+);
+CREATE TABLE revisions (
+    article_id TEXT NOT NULL,
+    seen_at TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    url TEXT NOT NULL,
+    rel TEXT NOT NULL,
+    title TEXT NOT NULL,
+    date TEXT NOT NULL,
+    body TEXT NOT NULL,
+    PRIMARY KEY (article_id, seen_at, fingerprint)
+);
+CREATE TABLE sent (
+    target TEXT NOT NULL,
+    article_id TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    PRIMARY KEY (target, article_id)
+);
+CREATE TABLE watermarks (
+    name TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+-- kindはkb, build, version, cveのどれか
+CREATE TABLE metadata (
+    article_id TEXT NOT NULL REFERENCES articles(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (article_id, kind, position)
+);
+-- 本文はHTMLを取り除いたテキストを索引にする
+CREATE VIRTUAL TABLE search USING fts5(
+    id UNINDEXED,
+    title,
+    body,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+"];
+
+/// An error while reading or writing a [`Store`].
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    // このプログラムより新しいバージョンが作ったデータベース
+    UnknownVersion(i64),
+}
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(e) => write!(f, "sqlite error: {}", e),
+            StoreError::UnknownVersion(v) => write!(f, "unknown schema version: {}", v),
+        }
+    }
+}
+impl std::error::Error for StoreError {}
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+// お知らせのある時点の内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    pub seen_at: String,
+    pub fingerprint: String,
+    pub article: Article,
+}
+
+fn metadata(a: &Article) -> [(&'static str, &Vec<String>); 4] {
+    [
+        ("kb", &a.kbs),
+        ("build", &a.builds),
+        ("version", &a.versions),
+        ("cve", &a.cves),
+    ]
+}
+
+fn metadata_mut<'a>(a: &'a mut Article, kind: &str) -> Option<&'a mut Vec<String>> {
+    match kind {
+        "kb" => Some(&mut a.kbs),
+        "build" => Some(&mut a.builds),
+        "version" => Some(&mut a.versions),
+        "cve" => Some(&mut a.cves),
+        _ => None,
+    }
+}
+
+fn read_articles(conn: &Connection) -> rusqlite::Result<BTreeMap<String, Archived>> {
+    let mut ret = BTreeMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT id, url, rel, title, date, body, summary, first_seen, last_changed, fingerprint FROM articles",
+    )?;
+    let rows = stmt.query_map([], |r| {
+        let mut article = Article::new(
+            r.get(0)?,
+            r.get(1)?,
+            article::static_rel(&r.get::<_, String>(2)?),
+            r.get(3)?,
+            r.get(4)?,
+            r.get(5)?,
+        );
+        article.summary = r.get(6)?;
+        Ok(Archived {
+            article,
+            first_seen: r.get(7)?,
+            last_changed: r.get(8)?,
+            fingerprint: r.get(9)?,
+        })
+    })?;
+    for archived in rows {
+        let archived = archived?;
+        ret.insert(archived.article.id.clone(), archived);
+    }
+    let mut stmt = conn.prepare(
+        "SELECT article_id, term, scheme, label FROM categories ORDER BY article_id, position",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(r) = rows.next()? {
+        let id: String = r.get(0)?;
+        if let Some(archived) = ret.get_mut(&id) {
+            archived.article.categories.push(Category {
+                term: r.get(1)?,
+                scheme: r.get(2)?,
+                label: r.get(3)?,
+            });
+        }
+    }
+    let mut stmt = conn.prepare(
+        "SELECT article_id, kind, value FROM metadata ORDER BY article_id, kind, position",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(r) = rows.next()? {
+        let id: String = r.get(0)?;
+        let kind: String = r.get(1)?;
+        if let Some(values) = ret
+            .get_mut(&id)
+            .and_then(|a| metadata_mut(&mut a.article, &kind))
+        {
+            values.push(r.get(2)?);
+        }
+    }
+    Ok(ret)
+}
+
+/// The SQLite database holding every announcement seen so far.
+pub struct Store {
+    conn: Connection,
+}
+impl Store {
+    pub fn open(path: &Path) -> Result<Self, StoreError> {
+        Self::migrate(Connection::open(path)?)
+    }
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::migrate(Connection::open_in_memory()?)
+    }
+    fn migrate(mut conn: Connection) -> Result<Self, StoreError> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        let version: i64 = conn.pragma_query_value(None, "user_version", |r| r.get(0))?;
+        if version > MIGRATIONS.len() as i64 {
+            return Err(StoreError::UnknownVersion(version));
+        }
+        for (i, sql) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let tx = conn.transaction()?;
+            tx.execute_batch(sql)?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+        Ok(Store { conn })
+    }
+    /// Reads the whole state. Revisions are read separately with [`Store::revisions`].
+    pub fn load(&self) -> Result<State, StoreError> {
+        let mut ret = State {
+            articles: read_articles(&self.conn)?,
+            ..Default::default()
+        };
+        let mut stmt = self
+            .conn
+            .prepare("SELECT target, article_id, fingerprint FROM sent")?;
+        for row in stmt.query_map([], |r| Ok(((r.get(0)?, r.get(1)?), r.get(2)?)))? {
+            let (key, fingerprint) = row?;
+            ret.sent.insert(key, fingerprint);
+        }
+        let mut stmt = self.conn.prepare("SELECT name, value FROM watermarks")?;
+        for row in stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))? {
+            let (name, value) = row?;
+            ret.watermarks.insert(name, value);
+        }
+        Ok(ret)
+    }
+    /// Reads the stored announcements with the given ids, in the same order.
+    /// Unknown ids are skipped.
+    pub fn articles(&self, ids: &[&str]) -> Result<Vec<Article>, StoreError> {
+        let mut all = read_articles(&self.conn)?;
+        Ok(ids
+            .iter()
+            .filter_map(|id| all.remove(*id))
+            .map(|a| a.article)
+            .collect())
+    }
+    fn index(tx: &Transaction, a: &Article) -> rusqlite::Result<()> {
+        tx.execute("DELETE FROM search WHERE id = ?1", [&a.id])?;
+        tx.execute(
+            "INSERT INTO search (id, title, body) VALUES (?1, ?2, ?3)",
+            params![a.id, a.title, a.body_text()],
+        )?;
+        Ok(())
+    }
+    fn insert_revision(tx: &Transaction, a: &Archived) -> rusqlite::Result<()> {
+        tx.execute(
+            "INSERT OR IGNORE INTO revisions (article_id, seen_at, fingerprint, url, rel, title, date, body)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                a.article.id,
+                a.last_changed,
+                a.fingerprint,
+                a.article.url,
+                a.article.rel,
+                a.article.title,
+                a.article.date,
+                a.article.body
+            ],
+        )?;
+        Ok(())
+    }
+    // 1つのトランザクションで書き込むので、途中で落ちても前回の状態が残る
+    // 保存済みのものと同じお知らせは書き込まず、索引もfingerprintが変わったときだけ作り直す
+    pub fn save(&self, state: &State) -> Result<(), StoreError> {
+        let tx = self.conn.unchecked_transaction()?;
+        let stored = read_articles(&tx)?;
+        for a in state.articles.values() {
+            let old = stored.get(&a.article.id);
+            if old == Some(a) {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO articles (id, url, rel, title, date, body, summary, first_seen, last_changed, fingerprint)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                 ON CONFLICT (id) DO UPDATE SET url = excluded.url, rel = excluded.rel, title = excluded.title,
+                     date = excluded.date, body = excluded.body, summary = excluded.summary,
+                     first_seen = excluded.first_seen, last_changed = excluded.last_changed,
+                     fingerprint = excluded.fingerprint",
+                params![
+                    a.article.id,
+                    a.article.url,
+                    a.article.rel,
+                    a.article.title,
+                    a.article.date,
+                    a.article.body,
+                    a.article.summary,
+                    a.first_seen,
+                    a.last_changed,
+                    a.fingerprint
+                ],
+            )?;
+            tx.execute(
+                "DELETE FROM categories WHERE article_id = ?1",
+                [&a.article.id],
+            )?;
+            for (i, c) in a.article.categories.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO categories (article_id, position, term, scheme, label) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![a.article.id, i, c.term, c.scheme, c.label],
+                )?;
+            }
+            tx.execute(
+                "DELETE FROM metadata WHERE article_id = ?1",
+                [&a.article.id],
+            )?;
+            for (kind, values) in metadata(&a.article) {
+                for (i, v) in values.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO metadata (article_id, kind, position, value) VALUES (?1, ?2, ?3, ?4)",
+                        params![a.article.id, kind, i, v],
+                    )?;
+                }
+            }
+            if old.is_none_or(|old| old.fingerprint != a.fingerprint) {
+                Self::insert_revision(&tx, a)?;
+                Self::index(&tx, &a.article)?;
+            }
+        }
+        for a in &state.revisions {
+            Self::insert_revision(&tx, a)?;
+        }
+        for ((target, id), fingerprint) in &state.sent {
+            tx.execute(
+                "INSERT OR REPLACE INTO sent (target, article_id, fingerprint) VALUES (?1, ?2, ?3)",
+                params![target, id, fingerprint],
+            )?;
+        }
+        for (name, value) in &state.watermarks {
+            tx.execute(
+                "INSERT OR REPLACE INTO watermarks (name, value) VALUES (?1, ?2)",
+                params![name, value],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    /// Runs a full-text search over the titles and bodies.
+    pub fn search(&self, query: &Query) -> Result<Vec<Hit>, StoreError> {
+        let mut sql = format!(
+            "SELECT a.id, a.title, a.date, a.url, snippet(search, -1, '{}', '{}', '…', 16)
+             FROM search JOIN articles a ON a.id = search.id
+             WHERE search MATCH ?1",
+            HIGHLIGHT_START, HIGHLIGHT_END
+        );
+        let mut values = vec![query.expr.clone()];
+        if let Some(since) = &query.since {
+            values.push(since.clone());
+            sql += &format!(" AND a.date >= ?{}", values.len());
+        }
+        if let Some(until) = &query.until {
+            values.push(until.clone());
+            sql += &format!(" AND a.date <= ?{}", values.len());
+        }
+        if !query.categories.is_empty() {
+            let mut placeholders = Vec::new();
+            for c in &query.categories {
+                values.push(c.clone());
+                placeholders.push(format!("?{}", values.len()));
+            }
+            sql += &format!(
+                " AND EXISTS (SELECT 1 FROM categories c WHERE c.article_id = a.id AND c.term IN ({}))",
+                placeholders.join(", ")
+            );
+        }
+        sql += match query.sort {
+            // タイトルに含まれる語を本文の語より重くする
+            Sort::Relevance => " ORDER BY bm25(search, 0.0, 10.0, 1.0), a.date DESC",
+            Sort::Date => " ORDER BY a.date DESC",
+        };
+        sql += &format!(" LIMIT {}", query.limit);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), |r| {
+            Ok(Hit {
+                id: r.get(0)?,
+                title: r.get(1)?,
+                date: r.get(2)?,
+                url: r.get(3)?,
+                snippet: r.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+    // 古いものから順に返す
+    pub fn revisions(&self, id: &str) -> Result<Vec<Revision>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT seen_at, fingerprint, url, rel, title, date, body FROM revisions
+             WHERE article_id = ?1 ORDER BY seen_at, fingerprint",
+        )?;
+        let rows = stmt.query_map([id], |r| {
+            Ok(Revision {
+                seen_at: r.get(0)?,
+                fingerprint: r.get(1)?,
+                article: Article::new(
+                    id.to_string(),
+                    r.get(2)?,
+                    article::static_rel(&r.get::<_, String>(3)?),
+                    r.get(4)?,
+                    r.get(5)?,
+                    r.get(6)?,
+                ),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+    #[cfg(test)]
+    fn version(&self) -> i64 {
+        self.conn
+            .pragma_query_value(None, "user_version", |r| r.get(0))
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    fn article(id: &str, body: &str) -> Article {
+        let mut a = testing::article(id, body);
+        a.summary = "Summary.".to_string();
+        a.categories = vec![
+            Category {
+                term: "reminder".to_string(),
+                scheme: "https://example.com/type".to_string(),
+                label: "Reminder".to_string(),
+            },
+            Category {
+                term: "windows-11".to_string(),
+                scheme: "https://example.com/product".to_string(),
+                label: "Windows 11".to_string(),
+            },
+        ];
+        a.kbs = vec!["5027231".to_string()];
+        a.cves = vec!["CVE-2022-37966".to_string(), "CVE-2022-37967".to_string()];
+        a
+    }
+    #[test]
+    fn save_and_load() {
+        let store = Store::open_in_memory().unwrap();
+        assert_eq!(store.version(), MIGRATIONS.len() as i64);
+        assert_eq!(store.load().unwrap(), State::default());
+        let mut state = State::default();
+        state.merge(
+            &[article("1", "a"), article("2", "b")],
+            "2023-06-13T00:00:00Z",
+        );
+        state.merge(&[article("2", "c")], "2023-06-14T00:00:00Z");
+        state.merge(&[article("2", "d")], "2023-06-15T00:00:00Z");
+        state.sent.insert(
+            ("slack".to_string(), "1".to_string()),
+            state.articles["1"].fingerprint.clone(),
+        );
+        state
+            .watermarks
+            .insert("digest".to_string(), "2023-06-13T00:00:00Z".to_string());
+        store.save(&state).unwrap();
+        // 2回保存しても版は増えず、変わっていないお知らせは書き込まない
+        let changes = store.conn.total_changes();
+        store.save(&state).unwrap();
+        assert_eq!(
+            store.conn.total_changes() - changes,
+            (state.sent.len() + state.watermarks.len()) as u64
+        );
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.articles, state.articles);
+        assert_eq!(
+            store.articles(&["2", "unknown", "1"]).unwrap(),
+            [
+                state.articles["2"].article.clone(),
+                state.articles["1"].article.clone()
+            ]
+        );
+        assert_eq!(loaded.articles["1"].article.cves.len(), 2);
+        assert_eq!(loaded.sent, state.sent);
+        assert_eq!(loaded.watermarks, state.watermarks);
+
+        let bodies: Vec<(String, String)> = store
+            .revisions("2")
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.seen_at, r.article.body))
+            .collect();
+        assert_eq!(
+            bodies,
+            [
+                ("2023-06-13T00:00:00Z".to_string(), "b".to_string()),
+                ("2023-06-14T00:00:00Z".to_string(), "c".to_string()),
+                ("2023-06-15T00:00:00Z".to_string(), "d".to_string()),
+            ]
+        );
+        assert_eq!(store.revisions("1").unwrap().len(), 1);
+    }
+    #[test]
+    fn search() {
+        let store = Store::open_in_memory().unwrap();
+        let mut state = State::default();
+        let mut netlogon = article("3113", "<div>Netlogon protocol changes.</div>");
+        netlogon.title = "Reminder: Security hardening changes for Netlogon and Kerberos".into();
+        netlogon.date = "2022-11-08T18:00:00Z".into();
+        let mut kerberos = article(
+            "3114",
+            "<div>Changes to <b>Kerberos</b> authentication are coming. Protect your network from CVE-2022-37966.</div>",
+        );
+        kerberos.title = "Upcoming changes".into();
+        kerberos.date = "2023-06-13T17:00:00Z".into();
+        kerberos.categories.truncate(1);
+        let update = article(
+            "3110",
+            "<div>The June 2023 security update is now available.</div>",
+        );
+        state.merge(&[netlogon, kerberos, update], "2023-06-14T00:00:00Z");
+        store.save(&state).unwrap();
+
+        let ids = |query: &Query| -> Vec<String> {
+            store
+                .search(query)
+                .unwrap()
+                .into_iter()
+                .map(|h| h.id)
+                .collect()
+        };
+        let mut query = Query::new("kerberos").unwrap();
+        // タイトルでの一致が先
+        assert_eq!(ids(&query), ["3113", "3114"]);
+        query.sort = Sort::Date;
+        assert_eq!(ids(&query), ["3114", "3113"]);
+        query.since = Some("2023-01-01T00:00:00Z".into());
+        assert_eq!(ids(&query), ["3114"]);
+        query.since = None;
+        query.until = Some("2022-12-31T23:59:59Z".into());
+        assert_eq!(ids(&query), ["3113"]);
+        query.until = None;
+        query.categories = vec!["windows-11".into()];
+        assert_eq!(ids(&query), ["3113"]);
+
+        assert_eq!(ids(&Query::new("kerberos -netlogon").unwrap()), ["3114"]);
+        // -netlogonの後の語もANDで効く
+        assert_eq!(
+            ids(&Query::new("kerberos -netlogon protect").unwrap()),
+            ["3114"]
+        );
+        assert_eq!(
+            ids(&Query::new("\"security update\" OR CVE-2022-37966").unwrap()).len(),
+            2
+        );
+        assert_eq!(ids(&Query::new("\"update security\"").unwrap()).len(), 0);
+        assert_eq!(ids(&Query::new("authenticat*").unwrap()), ["3114"]);
+
+        let hits = store.search(&Query::new("network").unwrap()).unwrap();
+        assert_eq!(hits[0].title, "Upcoming changes");
+        assert!(hits[0]
+            .snippet
+            .contains("Protect your \u{E000}network\u{E001} from"));
+    }
+    #[test]
+    fn unknown_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", 99).unwrap();
+        drop(conn);
+        assert!(matches!(
+            Store::open(&path),
+            Err(StoreError::UnknownVersion(99))
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+//! Summarizing the bodies of the announcements.
+
+use super::text;
+
+use scraper::{Html, Node};
+
+/// The default maximum length of a summary, in characters.
+pub const DEFAULT_LENGTH: usize = 200;
+
+// 段落の区切りになる要素
+const BLOCKS: [&str; 12] = [
+    "div", "p", "li", "ul", "ol", "br", "h1", "h2", "h3", "h4", "table", "tr",
+];
+
+struct Word {
+    text: String,
+    // 何番目のリンクの中にあるか
+    link: Option<usize>,
+}
+
+fn is_sentence_end(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', ')'])
+        .ends_with(['.', '!', '?'])
+}
+
+fn flush(current: &mut Option<Word>, ret: &mut Vec<Word>) {
+    if let Some(mut w) = current.take() {
+        w.text = text::normalize(&w.text);
+        if !w.text.is_empty() {
+            ret.push(w);
+        }
+    }
+}
+
+// 本文のHTMLを、どのリンクの中にあるかの情報付きで単語に分ける
+fn words(body: &str) -> Vec<Word> {
+    let html = Html::parse_fragment(body);
+    let mut ret: Vec<Word> = Vec::new();
+    let mut current: Option<Word> = None;
+    let mut links = 0;
+    for edge in html.root_element().traverse() {
+        let node = match edge {
+            ego_tree::iter::Edge::Open(n) => n,
+            ego_tree::iter::Edge::Close(n) => {
+                if n.value()
+                    .as_element()
+                    .is_some_and(|e| BLOCKS.contains(&e.name()))
+                {
+                    flush(&mut current, &mut ret);
+                }
+                continue;
+            }
+        };
+        match node.value() {
+            Node::Element(e) if e.name() == "a" => links += 1,
+            Node::Element(e) if BLOCKS.contains(&e.name()) => flush(&mut current, &mut ret),
+            Node::Text(t) => {
+                let link = node
+                    .ancestors()
+                    .any(|a| a.value().as_element().is_some_and(|e| e.name() == "a"))
+                    .then_some(links);
+                for c in t.chars() {
+                    if c.is_whitespace() {
+                        flush(&mut current, &mut ret);
+                        continue;
+                    }
+                    let w = current.get_or_insert_with(|| Word {
+                        text: String::new(),
+                        link,
+                    });
+                    // "<a>update</a>."のようにリンクの直後に続く文字は同じ単語として扱う
+                    w.link = w.link.or(link);
+                    w.text.push(c);
+                }
+            }
+            _ => {}
+        }
+    }
+    flush(&mut current, &mut ret);
+    ret
+}
+
+/// Writes the summary of an announcement from the first sentences of its body.
+#[derive(Debug)]
+pub struct Summarizer {
+    length: usize,
+}
+impl Default for Summarizer {
+    fn default() -> Self {
+        Self::new(DEFAULT_LENGTH)
+    }
+}
+impl Summarizer {
+    /// `length` is the maximum length of a summary, in characters.
+    pub fn new(length: usize) -> Self {
+        Summarizer { length }
+    }
+    /// Returns as many whole sentences from the start of `body` as fit in the length.
+    pub fn summarize(&self, body: &str) -> String {
+        let words = words(body);
+        let len = |ws: &[Word]| ws.iter().map(|w| w.text.chars().count() + 1).sum::<usize>() - 1;
+        let mut end = 0;
+        for (i, w) in words.iter().enumerate() {
+            if is_sentence_end(&w.text) || i + 1 == words.len() {
+                if len(&words[..=i]) > self.length {
+                    break;
+                }
+                end = i + 1;
+            }
+        }
+        if end > 0 {
+            return join(&words[..end]);
+        }
+        // 最初の文が長すぎる場合は単語の区切りで切り、リンクの途中では切らない
+        let mut end = 0;
+        while end < words.len() && len(&words[..=end]) < self.length {
+            end += 1;
+        }
+        while end > 0
+            && words
+                .get(end)
+                .is_some_and(|next| next.link.is_some() && next.link == words[end - 1].link)
+        {
+            end -= 1;
+        }
+        if end == 0 {
+            return String::new();
+        }
+        join(&words[..end]) + "…"
+    }
+}
+
+fn join(words: &[Word]) -> String {
+    words
+        .iter()
+        .map(|w| w.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    const BODY: &str = r#"<div>
+    The June 2023 security update release is now available
+    for Windows 11 and all supported versions of Windows
+    10. We recommend that you install these updates
+    promptly. For more information, see the&nbsp;<a href="https://support.microsoft.com/help/5027231"
+        >Windows 11 update history</a
+    >.</div><div>&nbsp;&nbsp;</div><ul><li>First</li><li>Second</li></ul>"#;
+    #[test]
+    fn summarize_sentences() {
+        assert_eq!(
+            Summarizer::new(200).summarize(BODY),
+            "The June 2023 security update release is now available for Windows 11 and all supported versions of Windows 10. We recommend that you install these updates promptly."
+        );
+        assert_eq!(
+            Summarizer::new(120).summarize(BODY),
+            "The June 2023 security update release is now available for Windows 11 and all supported versions of Windows 10."
+        );
+        assert_eq!(
+            Summarizer::new(1000).summarize(BODY),
+            "The June 2023 security update release is now available for Windows 11 and all supported versions of Windows 10. We recommend that you install these updates promptly. For more information, see the Windows 11 update history. First Second"
+        );
+    }
+    #[test]
+    fn truncate_long_sentence() {
+        assert_eq!(
+            Summarizer::new(40).summarize(BODY),
+            "The June 2023 security update release…"
+        );
+        let body = r#"<div>See the <a href="https://example.com">Windows 11 update history</a> page for details.</div>"#;
+        // "See the Windows 11"で切るとリンクの途中になる
+        assert_eq!(Summarizer::new(20).summarize(body), "See the…");
+        assert_eq!(Summarizer::new(5).summarize(body), "See…");
+        assert_eq!(Summarizer::new(2).summarize(body), "");
+    }
+}
@@ -0,0 +1,348 @@
+use super::article::Article;
+
+use chrono::{DateTime, TimeZone};
+use ego_tree::iter::Edge;
+use scraper::{Html, Node};
+use std::fmt::Display;
+
+pub const DEFAULT_WIDTH: usize = 80;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+// 段落を区切る要素
+const BLOCKS: [&str; 13] = [
+    "div",
+    "p",
+    "ul",
+    "ol",
+    "table",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "pre",
+    "blockquote",
+];
+
+struct Line {
+    // 1行目の前に付ける記号 (箇条書きの"- "など) と、2行目以降の字下げ
+    marker: String,
+    indent: usize,
+    text: String,
+    blank_before: bool,
+}
+
+// 本文のHTMLを行の列と、脚注にするリンクの列に変換する
+#[derive(Default)]
+struct Body {
+    lines: Vec<Line>,
+    links: Vec<String>,
+    current: String,
+    marker: String,
+    depth: usize,
+    blank_before: bool,
+}
+impl Body {
+    fn push_text(&mut self, s: &str) {
+        for (i, word) in s.split(char::is_whitespace).enumerate() {
+            if i > 0 && !self.current.is_empty() && !self.current.ends_with(' ') {
+                self.current.push(' ');
+            }
+            self.current += word;
+        }
+    }
+    fn flush(&mut self) {
+        let text = self
+            .current
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.current.clear();
+        if text.is_empty() {
+            return;
+        }
+        let indent = self.depth.saturating_sub(1) * 2;
+        let marker = format!("{}{}", " ".repeat(indent), std::mem::take(&mut self.marker));
+        self.lines.push(Line {
+            indent: marker.chars().count(),
+            marker,
+            text,
+            blank_before: self.blank_before && !self.lines.is_empty(),
+        });
+        self.blank_before = false;
+    }
+    fn parse(body: &str) -> Self {
+        let html = Html::parse_fragment(body);
+        let mut ret = Body::default();
+        for edge in html.root_element().traverse() {
+            match edge {
+                Edge::Open(node) => match node.value() {
+                    Node::Text(t) => ret.push_text(t),
+                    Node::Element(e) => match e.name() {
+                        "br" | "tr" => ret.flush(),
+                        "ul" | "ol" => {
+                            ret.flush();
+                            ret.depth += 1;
+                            ret.blank_before |= ret.depth == 1;
+                        }
+                        "li" => {
+                            ret.flush();
+                            ret.marker = "- ".to_string();
+                        }
+                        "td" | "th" if !ret.current.trim().is_empty() => ret.current += " | ",
+                        name if BLOCKS.contains(&name) => {
+                            ret.flush();
+                            ret.blank_before = true;
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                },
+                Edge::Close(node) => {
+                    let Some(e) = node.value().as_element() else {
+                        continue;
+                    };
+                    match e.name() {
+                        "a" => {
+                            let Some(href) = e.attr("href") else {
+                                continue;
+                            };
+                            let has_text = node
+                                .descendants()
+                                .filter_map(|n| n.value().as_text())
+                                .any(|t| !t.trim().is_empty());
+                            if !has_text {
+                                continue;
+                            }
+                            // 同じURLには同じ番号を振る
+                            let n = match ret.links.iter().position(|l| l == href) {
+                                Some(i) => i + 1,
+                                None => {
+                                    ret.links.push(href.to_string());
+                                    ret.links.len()
+                                }
+                            };
+                            let trailing = ret.current.ends_with(' ');
+                            ret.current.truncate(ret.current.trim_end().len());
+                            ret.current += &format!("[{}]", n);
+                            if trailing {
+                                ret.current.push(' ');
+                            }
+                        }
+                        "li" | "tr" => ret.flush(),
+                        "ul" | "ol" => {
+                            ret.flush();
+                            ret.depth -= 1;
+                            ret.blank_before |= ret.depth == 0;
+                        }
+                        name if BLOCKS.contains(&name) => {
+                            ret.flush();
+                            ret.blank_before = true;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        ret.flush();
+        ret
+    }
+}
+
+// フィードの文字列にESCなどの制御文字があると端末を操作できてしまうので、改行以外は取り除く
+fn printable(s: &str) -> String {
+    s.chars()
+        .filter(|&c| c == '\n' || !c.is_control())
+        .collect()
+}
+
+// 単語の区切りで折り返す。widthより長い単語はそのまま1行にする
+fn wrap(text: &str, width: usize, marker: &str, indent: usize) -> Vec<String> {
+    let text = printable(text);
+    let mut ret = Vec::new();
+    let mut line = marker.to_string();
+    let mut line_len = marker.chars().count();
+    let mut empty = true;
+    for word in text.split(' ') {
+        let len = word.chars().count();
+        if !empty && line_len + 1 + len > width {
+            ret.push(line);
+            line = " ".repeat(indent);
+            line_len = indent;
+            empty = true;
+        }
+        if !empty {
+            line.push(' ');
+            line_len += 1;
+        }
+        line += word;
+        line_len += len;
+        empty = false;
+    }
+    ret.push(line);
+    ret
+}
+
+#[derive(Debug)]
+pub struct Terminal {
+    width: usize,
+    color: bool,
+}
+impl Default for Terminal {
+    fn default() -> Self {
+        Self::new(DEFAULT_WIDTH)
+    }
+}
+impl Terminal {
+    pub fn new(width: usize) -> Self {
+        Terminal {
+            width,
+            color: false,
+        }
+    }
+    pub fn color(self, color: bool) -> Self {
+        Terminal { color, ..self }
+    }
+    fn paint(&self, style: &str, s: &str) -> String {
+        let s = printable(s);
+        if self.color {
+            format!("{}{}{}", style, s, RESET)
+        } else {
+            s
+        }
+    }
+    // 日時はtzの時刻で表示する
+    pub fn render<Tz: TimeZone>(&self, articles: &[Article], tz: &Tz) -> String
+    where
+        Tz::Offset: Display,
+    {
+        let mut ret = String::new();
+        for (i, a) in articles.iter().enumerate() {
+            if i > 0 {
+                ret += "\n";
+            }
+            for l in wrap(&a.title, self.width, "", 0) {
+                ret += &self.paint(BOLD, &l);
+                ret += "\n";
+            }
+            let date = DateTime::parse_from_rfc3339(&a.date).map_or_else(
+                |_| a.date.clone(),
+                |d| {
+                    d.with_timezone(tz)
+                        .format("%Y-%m-%d %H:%M (%:z)")
+                        .to_string()
+                },
+            );
+            ret += &self.paint(DIM, &date);
+            ret += "\n";
+            ret += &self.paint(CYAN, &a.url);
+            ret += "\n";
+            let body = Body::parse(&a.body);
+            for (j, line) in body.lines.iter().enumerate() {
+                if j == 0 || line.blank_before {
+                    ret += "\n";
+                }
+                for l in wrap(&line.text, self.width, &line.marker, line.indent) {
+                    ret += &l;
+                    ret += "\n";
+                }
+            }
+            if !body.links.is_empty() {
+                ret += "\n";
+            }
+            for (j, link) in body.links.iter().enumerate() {
+                ret += &self.paint(DIM, &format!("[{}]", j + 1));
+                ret += " ";
+                ret += &self.paint(CYAN, link);
+                ret += "\n";
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    use chrono::FixedOffset;
+    fn article() -> Article {
+        testing::article(
+            "3110",
+            r#"<div>
+    The June 2023 security update release is now available
+    for Windows 11. See the&nbsp;<a href="https://support.microsoft.com/help/5027231"
+        >Windows 11</a
+    >&nbsp;and <a href="https://support.microsoft.com/help/5027215">Windows 10</a>
+    pages.<br>Second line, <a href="https://support.microsoft.com/help/5027231">again</a>.</div>
+<div><strong>Highlights:</strong></div>
+<ul><li>First item which is long enough to be wrapped at the width</li><li>Second<ul><li>Nested</li></ul></li></ul>
+<table><tr><td>22H2</td><td>22621.1848</td></tr></table>"#,
+        )
+    }
+    const PLAIN: &str = "Take action: June 2023 security update
+is now available
+2023-06-14 02:00 (+09:00)
+https://support.microsoft.com/help/5027231
+
+The June 2023 security update release is
+now available for Windows 11. See the
+Windows 11[1] and Windows 10[2] pages.
+Second line, again[1].
+
+Highlights:
+
+- First item which is long enough to be
+  wrapped at the width
+- Second
+  - Nested
+
+22H2 | 22621.1848
+
+[1] https://support.microsoft.com/help/5027231
+[2] https://support.microsoft.com/help/5027215
+";
+    #[test]
+    fn render_plain() {
+        let jst = FixedOffset::east_opt(9 * 3600).unwrap();
+        let terminal = Terminal::new(40);
+        assert_eq!(terminal.render(&[article()], &jst), PLAIN);
+        assert_eq!(
+            terminal.render(&[article(), article()], &jst),
+            format!("{}\n{}", PLAIN, PLAIN)
+        );
+    }
+    #[test]
+    fn render_color() {
+        let out = Terminal::default()
+            .color(true)
+            .render(&[article()], &chrono::Utc);
+        assert!(out.starts_with(
+            "\x1b[1mTake action: June 2023 security update is now available\x1b[0m\n\x1b[2m2023-06-13 17:00 (+00:00)\x1b[0m\n"
+        ));
+        assert!(out.ends_with(
+            "\x1b[2m[2]\x1b[0m \x1b[36mhttps://support.microsoft.com/help/5027215\x1b[0m\n"
+        ));
+    }
+    #[test]
+    fn strip_control_characters() {
+        let mut a = article();
+        a.title = "Take\x1b]0;pwned\x07 action\u{9b}2J".to_string();
+        a.url = "https://example.com/\x1b[2J".to_string();
+        a.body = "<p>Body\x1b[31m text\r</p><a href=\"https://example.com/\x08\">x</a>".to_string();
+        let out = Terminal::new(80).render(&[a], &chrono::Utc);
+        assert_eq!(
+            out,
+            "Take]0;pwned action2J\n2023-06-13 17:00 (+00:00)\nhttps://example.com/[2J\n\nBody[31m text\n\nx[1]\n\n[1] https://example.com/\n"
+        );
+        assert!(Terminal::default()
+            .color(true)
+            .render(&[article()], &chrono::Utc)
+            .contains('\x1b'));
+    }
+}
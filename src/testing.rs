@@ -0,0 +1,14 @@
+use super::article::Article;
+
+// テストで使うお知らせ。URL、タイトル、日時はJune 2023のセキュリティ更新のもの。
+// 違う値が要るテストは Article { title, ..article(id, body) } のように上書きする
+pub fn article(id: &str, body: &str) -> Article {
+    Article::new(
+        id.to_string(),
+        "https://support.microsoft.com/help/5027231".to_string(),
+        "alternate",
+        "Take action: June 2023 security update is now available".to_string(),
+        "2023-06-13T17:00:00Z".to_string(),
+        body.to_string(),
+    )
+}
@@ -0,0 +1,61 @@
+use unicode_normalization::UnicodeNormalization;
+
+// タイトルや本文のテキストは、表示や検索の前に必ずここを通す
+// - NBSP等の幅のある空白は普通の空白に、ゼロ幅文字は削除
+// - NFCに正規化
+// - 連続する空白を1つにまとめ、前後の空白を除く
+// - “”‘’は"'に置き換える (フィルタやタイトルの比較で表記揺れにならないように)
+pub fn normalize(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    let mut pending_space = false;
+    for c in s.nfc() {
+        let c = match c {
+            '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{2060}' | '\u{feff}' => continue,
+            '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' | '\u{2032}' => '\'',
+            '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{2033}' => '"',
+            c if c.is_whitespace() => {
+                pending_space = true;
+                continue;
+            }
+            c => c,
+        };
+        if pending_space && !ret.is_empty() {
+            ret.push(' ');
+        }
+        pending_space = false;
+        ret.push(c);
+    }
+    ret
+}
+
+// ユーザーが付けた名前をカテゴリのtermにする。category=で絞り込むときもこの形で比べる
+pub fn slug(label: &str) -> String {
+    label.to_lowercase().replace(' ', "-")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn normalize_text() {
+        assert_eq!(
+            normalize(
+                "  Reminder: End of servicing for Windows 10, version\n        21H2\u{a0}Home  "
+            ),
+            "Reminder: End of servicing for Windows 10, version 21H2 Home"
+        );
+        assert_eq!(normalize("zero\u{200b}width\u{feff}"), "zerowidth");
+        assert_eq!(
+            normalize("\u{201c}Document not saved.\u{201d} Microsoft\u{2019}s"),
+            "\"Document not saved.\" Microsoft's"
+        );
+        // e + 結合アキュート → é
+        assert_eq!(normalize("Cafe\u{301}"), "Caf\u{e9}");
+        assert_eq!(normalize("\u{a0}\u{2009}\u{3000}"), "");
+    }
+    #[test]
+    fn slug_label() {
+        assert_eq!(slug("Sales Laptops"), "sales-laptops");
+        assert_eq!(slug("servers"), "servers");
+    }
+}
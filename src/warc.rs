@@ -0,0 +1,268 @@
+use super::fetch::Response;
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const WARC_VERSION: &str = "WARC/1.1";
+// reqwestが展開済みのbodyを渡してくるので、元のヘッダはそのまま残すと矛盾する
+const REWRITTEN_HEADERS: [&str; 3] = ["content-encoding", "content-length", "transfer-encoding"];
+
+pub struct WarcWriter {
+    path: PathBuf,
+}
+impl WarcWriter {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        WarcWriter { path: path.into() }
+    }
+    pub fn write(&self, response: &Response, user_agent: &str) -> io::Result<()> {
+        let is_new = fs::metadata(&self.path).map_or(true, |m| m.len() == 0);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut buf = Vec::new();
+        if is_new {
+            let fields = format!(
+                "software: {}/{}\r\nformat: WARC File Format 1.1\r\n",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            );
+            write_record(
+                &mut buf,
+                &[
+                    ("WARC-Type", "warcinfo"),
+                    ("WARC-Filename", &file_name(&self.path)),
+                    ("Content-Type", "application/warc-fields"),
+                ],
+                &response.timestamp,
+                fields.as_bytes(),
+            );
+        }
+        let response_id = record_id();
+        write_record(
+            &mut buf,
+            &[
+                ("WARC-Type", "request"),
+                ("WARC-Target-URI", &response.url),
+                ("WARC-Concurrent-To", &response_id),
+                ("Content-Type", "application/http;msgtype=request"),
+            ],
+            &response.timestamp,
+            request_block(&response.url, user_agent).as_bytes(),
+        );
+        write_record(
+            &mut buf,
+            &[
+                ("WARC-Type", "response"),
+                ("WARC-Record-ID", &response_id),
+                ("WARC-Target-URI", &response.url),
+                ("Content-Type", "application/http;msgtype=response"),
+            ],
+            &response.timestamp,
+            &response_block(response),
+        );
+        file.write_all(&buf)
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(String::new, |s| s.to_string_lossy().into_owned())
+}
+
+fn record_id() -> String {
+    format!("<urn:uuid:{}>", Uuid::new_v4())
+}
+
+fn write_record(buf: &mut Vec<u8>, headers: &[(&str, &str)], date: &str, block: &[u8]) {
+    let mut head = format!("{}\r\n", WARC_VERSION);
+    if !headers.iter().any(|(name, _)| *name == "WARC-Record-ID") {
+        head += &format!("WARC-Record-ID: {}\r\n", record_id());
+    }
+    head += &format!("WARC-Date: {}\r\n", date);
+    for (name, value) in headers {
+        head += &format!("{}: {}\r\n", name, value);
+    }
+    head += &format!("Content-Length: {}\r\n\r\n", block.len());
+    buf.extend_from_slice(head.as_bytes());
+    buf.extend_from_slice(block);
+    buf.extend_from_slice(b"\r\n\r\n");
+}
+
+fn request_block(url: &str, user_agent: &str) -> String {
+    let (host, path) = match reqwest::Url::parse(url) {
+        Ok(u) => {
+            let mut path = u.path().to_string();
+            if let Some(q) = u.query() {
+                path += "?";
+                path += q;
+            }
+            (u.host_str().unwrap_or_default().to_string(), path)
+        }
+        Err(_) => (String::new(), url.to_string()),
+    };
+    format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\n\r\n",
+        path, host, user_agent
+    )
+}
+
+fn response_block(response: &Response) -> Vec<u8> {
+    let reason = reqwest::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or_default();
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, reason);
+    for (name, value) in &response.headers {
+        if REWRITTEN_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            head += &format!("X-Archive-Orig-{}: {}\r\n", name, value);
+        } else {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+    }
+    head += &format!("Content-Length: {}\r\n\r\n", response.body.len());
+    let mut ret = head.into_bytes();
+    ret.extend_from_slice(response.body.as_bytes());
+    ret
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WarcRecord {
+    pub headers: Vec<(String, String)>,
+    pub block: Vec<u8>,
+}
+impl WarcRecord {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn http_body(&self) -> Option<String> {
+        let pos = self.block.windows(4).position(|w| w == b"\r\n\r\n")?;
+        Some(String::from_utf8_lossy(&self.block[pos + 4..]).into_owned())
+    }
+}
+
+fn read_line(data: &[u8], pos: &mut usize) -> Option<String> {
+    let rest = &data[*pos..];
+    let end = rest.windows(2).position(|w| w == b"\r\n")?;
+    *pos += end + 2;
+    Some(String::from_utf8_lossy(&rest[..end]).into_owned())
+}
+
+pub fn read_records(data: &[u8]) -> Result<Vec<WarcRecord>, String> {
+    let mut ret = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let version = read_line(data, &mut pos).ok_or("truncated record header")?;
+        if version.is_empty() {
+            continue;
+        }
+        if !version.starts_with("WARC/") {
+            return Err(format!("unexpected line at byte {}: {}", pos, version));
+        }
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(data, &mut pos).ok_or("truncated record header")?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("malformed header: {}", line))?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+        let length: usize = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, v)| v.parse().ok())
+            .ok_or("missing Content-Length")?;
+        let block = data
+            .get(pos..pos + length)
+            .ok_or("truncated record block")?
+            .to_vec();
+        pos += length;
+        ret.push(WarcRecord { headers, block });
+    }
+    Ok(ret)
+}
+
+// 同じURLのレスポンスを記録順に返す
+pub fn read_responses(path: &Path, url: &str) -> Result<Vec<(String, String)>, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(read_records(&data)?
+        .into_iter()
+        .filter(|r| r.header("WARC-Type") == Some("response"))
+        .filter(|r| r.header("WARC-Target-URI") == Some(url))
+        .filter_map(|r| {
+            let date = r.header("WARC-Date")?.to_string();
+            Some((date, r.http_body()?))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn response(body: &str, timestamp: &str) -> Response {
+        Response {
+            url: "https://learn.microsoft.com/en-us/windows/release-health/windows-message-center"
+                .to_string(),
+            status: 200,
+            headers: vec![
+                ("content-type".to_string(), "text/html".to_string()),
+                ("content-encoding".to_string(), "gzip".to_string()),
+            ],
+            body: body.to_string(),
+            timestamp: timestamp.to_string(),
+        }
+    }
+    #[test]
+    fn write_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.warc");
+        let writer = WarcWriter::new(&path);
+        let first = response("<html>first</html>", "2023-06-13T00:00:00Z");
+        let second = response("<html>second\r\n\r\n</html>", "2023-06-14T00:00:00Z");
+        writer.write(&first, "test-agent").unwrap();
+        writer.write(&second, "test-agent").unwrap();
+
+        let records = read_records(&fs::read(&path).unwrap()).unwrap();
+        let types: Vec<_> = records
+            .iter()
+            .map(|r| r.header("WARC-Type").unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            ["warcinfo", "request", "response", "request", "response"]
+        );
+        assert_eq!(
+            records[1].header("WARC-Concurrent-To"),
+            records[2].header("WARC-Record-ID")
+        );
+        let request = String::from_utf8(records[1].block.clone()).unwrap();
+        assert!(request.starts_with(
+            "GET /en-us/windows/release-health/windows-message-center HTTP/1.1\r\nHost: learn.microsoft.com\r\n"
+        ));
+        let head = String::from_utf8_lossy(&records[2].block);
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("X-Archive-Orig-content-encoding: gzip\r\n"));
+
+        let responses = read_responses(&path, &first.url).unwrap();
+        assert_eq!(
+            responses,
+            [
+                ("2023-06-13T00:00:00Z".to_string(), first.body),
+                ("2023-06-14T00:00:00Z".to_string(), second.body)
+            ]
+        );
+    }
+    #[test]
+    fn read_truncated() {
+        let data = b"WARC/1.1\r\nWARC-Type: response\r\nContent-Length: 100\r\n\r\nshort";
+        assert!(read_records(data).is_err());
+    }
+}
@@ -0,0 +1,483 @@
+use super::article::Article;
+use super::config::Ini;
+use super::json::Json;
+use super::state::{self, Change, State};
+
+use reqwest::blocking::Client;
+use std::thread;
+use std::time::Duration;
+
+// 設定で指定できる再送の回数と、再送までの待ち時間の上限
+const MAX_RETRIES: u64 = 10;
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    Slack,
+    Teams,
+    Discord,
+    // {{title}}などを埋め込むJSONのテンプレート
+    Json(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Webhook {
+    pub name: String,
+    pub url: String,
+    pub kind: Kind,
+    // 失敗したときに再送する回数と、最初の再送までの待ち時間 (再送ごとに倍になる)
+    pub retries: usize,
+    pub backoff: Duration,
+}
+impl Webhook {
+    pub fn new(name: String, url: String, kind: Kind) -> Self {
+        Webhook {
+            name,
+            url,
+            kind,
+            retries: 3,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+// テンプレートの{{change}}に埋め込む値
+fn change_term(change: Change) -> &'static str {
+    match change {
+        Change::New => "new",
+        Change::Changed => "changed",
+    }
+}
+
+fn change_label(change: Change) -> &'static str {
+    match change {
+        Change::New => "New",
+        Change::Changed => "Updated",
+    }
+}
+
+// Slackのmrkdwnで特別な意味を持つ文字
+fn slack_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// テンプレートに埋め込む値は、JSONの文字列の中身としてエスケープする
+fn json_escape(s: &str) -> String {
+    let quoted = Json::from(s).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+pub fn payload(kind: &Kind, a: &Article, change: Change) -> String {
+    match kind {
+        Kind::Slack => Json::object(vec![(
+            "text",
+            format!(
+                "{}: *<{}|{}>*\n{}",
+                change_label(change),
+                // URLの|はリンクの区切りとみなされる
+                slack_escape(&a.url).replace('|', "%7C"),
+                slack_escape(&a.title),
+                slack_escape(&a.summary)
+            )
+            .into(),
+        )]),
+        Kind::Teams => Json::object(vec![
+            ("type", "message".into()),
+            (
+                "attachments",
+                Json::Array(vec![Json::object(vec![
+                    (
+                        "contentType",
+                        "application/vnd.microsoft.card.adaptive".into(),
+                    ),
+                    ("contentUrl", Json::Null),
+                    (
+                        "content",
+                        Json::object(vec![
+                            (
+                                "$schema",
+                                "http://adaptivecards.io/schemas/adaptive-card.json".into(),
+                            ),
+                            ("type", "AdaptiveCard".into()),
+                            ("version", "1.4".into()),
+                            (
+                                "body",
+                                Json::Array(vec![
+                                    Json::object(vec![
+                                        ("type", "TextBlock".into()),
+                                        ("text", a.title.as_str().into()),
+                                        ("weight", "Bolder".into()),
+                                        ("size", "Medium".into()),
+                                        ("wrap", true.into()),
+                                    ]),
+                                    Json::object(vec![
+                                        ("type", "TextBlock".into()),
+                                        (
+                                            "text",
+                                            format!("{} · {}", change_label(change), a.date).into(),
+                                        ),
+                                        ("isSubtle", true.into()),
+                                        ("spacing", "None".into()),
+                                        ("wrap", true.into()),
+                                    ]),
+                                    Json::object(vec![
+                                        ("type", "TextBlock".into()),
+                                        ("text", a.summary.as_str().into()),
+                                        ("wrap", true.into()),
+                                    ]),
+                                ]),
+                            ),
+                            (
+                                "actions",
+                                Json::Array(vec![Json::object(vec![
+                                    ("type", "Action.OpenUrl".into()),
+                                    ("title", "Open".into()),
+                                    ("url", a.url.as_str().into()),
+                                ])]),
+                            ),
+                        ]),
+                    ),
+                ])]),
+            ),
+        ]),
+        Kind::Discord => Json::object(vec![
+            (
+                "content",
+                format!("{} announcement", change_label(change)).into(),
+            ),
+            (
+                "embeds",
+                Json::Array(vec![Json::object(vec![
+                    ("title", a.title.as_str().into()),
+                    ("url", a.url.as_str().into()),
+                    ("description", a.summary.as_str().into()),
+                    ("timestamp", a.date.as_str().into()),
+                ])]),
+            ),
+        ]),
+        Kind::Json(template) => {
+            let mut ret = template.clone();
+            for (name, value) in [
+                ("id", a.id.as_str()),
+                ("title", &a.title),
+                ("url", &a.url),
+                ("date", &a.date),
+                ("summary", &a.summary),
+                ("change", change_term(change)),
+            ] {
+                ret = ret.replace(&format!("{{{{{}}}}}", name), &json_escape(value));
+            }
+            return ret;
+        }
+    }
+    .to_string()
+}
+
+// attempt回目の再送までの待ち時間。再送ごとに倍にし、MAX_BACKOFFで止める
+fn delay(backoff: Duration, attempt: usize) -> Duration {
+    u32::try_from(attempt - 1)
+        .ok()
+        .and_then(|n| 1u32.checked_shl(n))
+        .and_then(|m| backoff.checked_mul(m))
+        .map_or(MAX_BACKOFF, |d| d.min(MAX_BACKOFF))
+}
+
+#[derive(Debug)]
+pub struct Webhooks {
+    hooks: Vec<Webhook>,
+}
+impl Webhooks {
+    pub fn new(hooks: Vec<Webhook>) -> Self {
+        Webhooks { hooks }
+    }
+    // 共通の設定ファイルの[webhook NAME]のセクションごとに、kind, url (kind = jsonならtemplateも) を書く
+    pub fn from_config(ini: &Ini) -> Result<Self, String> {
+        let mut hooks = Vec::new();
+        for (name, section) in ini.sections_of("webhook") {
+            if let Some(e) = section.entries.iter().find(|e| {
+                !matches!(
+                    e.key.as_str(),
+                    "kind" | "url" | "template" | "retries" | "backoff"
+                )
+            }) {
+                return Err(format!("[{}]: unknown key: {}", name, e.key));
+            }
+            let value = |key: &str| section.value(key).map(str::to_string);
+            let url = value("url").ok_or_else(|| format!("[{}]: url is required", name))?;
+            let kind = match value("kind").as_deref() {
+                Some("slack") => Kind::Slack,
+                Some("teams") => Kind::Teams,
+                Some("discord") => Kind::Discord,
+                Some("json") => Kind::Json(
+                    value("template").ok_or_else(|| format!("[{}]: template is required", name))?,
+                ),
+                Some(k) => return Err(format!("[{}]: unknown kind: {}", name, k)),
+                None => return Err(format!("[{}]: kind is required", name)),
+            };
+            let number = |key: &str| match value(key) {
+                Some(v) => v
+                    .parse::<u64>()
+                    .map(Some)
+                    .map_err(|_| format!("[{}]: {} must be a number: {}", name, key, v)),
+                None => Ok(None),
+            };
+            let mut hook = Webhook::new(name.to_string(), url, kind);
+            if let Some(n) = number("retries")? {
+                if n > MAX_RETRIES {
+                    return Err(format!(
+                        "[{}]: retries must be at most {}: {}",
+                        name, MAX_RETRIES, n
+                    ));
+                }
+                hook.retries = n as usize;
+            }
+            if let Some(n) = number("backoff")? {
+                hook.backoff = Duration::from_secs(n);
+            }
+            hooks.push(hook);
+        }
+        Ok(Webhooks::new(hooks))
+    }
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+    fn post(&self, client: &Client, hook: &Webhook, body: String) -> Result<(), String> {
+        let mut last_error = String::new();
+        for attempt in 0..=hook.retries {
+            if attempt > 0 {
+                thread::sleep(delay(hook.backoff, attempt));
+            }
+            match client
+                .post(&hook.url)
+                .header("Content-Type", "application/json")
+                .body(body.clone())
+                .send()
+            {
+                Ok(res) if res.status().is_success() => return Ok(()),
+                Ok(res) => last_error = format!("HTTP {}", res.status()),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(format!("{}: {}", hook.name, last_error))
+    }
+    // 前回通知したときから変わったお知らせを通知し、stateに記録する
+    // 初めて使う通知先には、今あるお知らせを通知せずに記録だけする
+    pub fn notify(&self, client: &Client, articles: &[Article], state: &mut State) -> Vec<String> {
+        let mut errors = Vec::new();
+        for hook in &self.hooks {
+            let first_use = !state.sent.keys().any(|(name, _)| *name == hook.name);
+            // お知らせがなくても、次回からは通知するように空のidで使ったことを記録する
+            if first_use {
+                state
+                    .sent
+                    .insert((hook.name.clone(), String::new()), String::new());
+            }
+            // 古いお知らせから順に通知する
+            for a in articles.iter().rev() {
+                let fingerprint = state::fingerprint(a);
+                let key = (hook.name.clone(), a.id.clone());
+                let change = match state.sent.get(&key) {
+                    Some(sent) if *sent == fingerprint => continue,
+                    Some(_) => Change::Changed,
+                    None => Change::New,
+                };
+                if !first_use {
+                    if let Err(e) = self.post(client, hook, payload(&hook.kind, a, change)) {
+                        errors.push(e);
+                        continue;
+                    }
+                }
+                state.sent.insert(key, fingerprint);
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    use std::sync::mpsc;
+    use tiny_http::{Response, Server};
+
+    fn article(id: &str, body: &str) -> Article {
+        let mut a = Article {
+            title: "Take action: June 2023 <security> update".to_string(),
+            ..testing::article(id, body)
+        };
+        a.summary = "The \"June\" update & more.".to_string();
+        a
+    }
+    #[test]
+    fn payloads() {
+        let a = article("3110", "");
+        assert_eq!(
+            payload(&Kind::Slack, &a, Change::New),
+            r#"{"text":"New: *\u003chttps://support.microsoft.com/help/5027231|Take action: June 2023 &lt;security&gt; update>*\nThe \"June\" update &amp; more."}"#
+        );
+        let b = Article {
+            url: "https://example.com/a?x=1&y=2|3>".to_string(),
+            ..article("3111", "")
+        };
+        assert!(payload(&Kind::Slack, &b, Change::New)
+            .contains(r#"\u003chttps://example.com/a?x=1&amp;y=2%7C3&gt;|Take action"#));
+        let teams = payload(&Kind::Teams, &a, Change::Changed);
+        assert!(teams.contains(r#""contentType":"application/vnd.microsoft.card.adaptive""#));
+        assert!(teams.contains(r#""text":"Updated · 2023-06-13T17:00:00Z""#));
+        assert!(teams.contains(
+            r#""actions":[{"type":"Action.OpenUrl","title":"Open","url":"https://support.microsoft.com/help/5027231"}]"#
+        ));
+        assert_eq!(
+            payload(&Kind::Discord, &a, Change::New),
+            r#"{"content":"New announcement","embeds":[{"title":"Take action: June 2023 \u003csecurity> update","url":"https://support.microsoft.com/help/5027231","description":"The \"June\" update & more.","timestamp":"2023-06-13T17:00:00Z"}]}"#
+        );
+        assert_eq!(
+            payload(
+                &Kind::Json(
+                    r#"{"event": "{{change}}", "id": "{{id}}", "text": "{{summary}}"}"#.to_string()
+                ),
+                &a,
+                Change::Changed
+            ),
+            r#"{"event": "changed", "id": "3110", "text": "The \"June\" update & more."}"#
+        );
+    }
+    fn parse(s: &str) -> Result<Webhooks, String> {
+        Webhooks::from_config(&Ini::parse(s)?)
+    }
+    #[test]
+    fn parse_config() {
+        let hooks = parse(
+            r#"
+# 運用チーム
+[webhook ops]
+kind = slack
+url = https://hooks.slack.com/services/T/B/X
+
+[webhook custom]
+kind = json
+url = http://localhost:8080/hook
+template = {"title": "{{title}}"}
+retries = 5
+backoff = 10
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            hooks.hooks,
+            [
+                Webhook::new(
+                    "ops".to_string(),
+                    "https://hooks.slack.com/services/T/B/X".to_string(),
+                    Kind::Slack,
+                ),
+                Webhook {
+                    name: "custom".to_string(),
+                    url: "http://localhost:8080/hook".to_string(),
+                    kind: Kind::Json(r#"{"title": "{{title}}"}"#.to_string()),
+                    retries: 5,
+                    backoff: Duration::from_secs(10),
+                },
+            ]
+        );
+        let hooks =
+            parse("[webhook ops]\nkind = slack\nurl = x\n[rule r]\nif = title contains x").unwrap();
+        assert_eq!(hooks.hooks.len(), 1);
+        assert_eq!(hooks.hooks[0].name, "ops");
+        assert!(parse("[webhook a]\nkind = slack").is_err());
+        assert!(parse("[webhook a]\nkind = irc\nurl = x").is_err());
+        assert!(parse("[webhook a]\nkind = json\nurl = x").is_err());
+        assert!(parse("[webhook a]\nkind = slack\nurl = x\nchannel = y").is_err());
+        assert!(parse("[webhook a]\nkind = slack\nurl = x\nretries = -1").is_err());
+        assert!(parse("[webhook a]\nkind = slack\nurl = x\nretries = 11").is_err());
+        assert!(parse(
+            "[webhook a]\nkind = slack\nurl = x\nretries = 10\nbackoff = 18446744073709551615"
+        )
+        .is_ok());
+    }
+    #[test]
+    fn backoff() {
+        let second = Duration::from_secs(1);
+        assert_eq!(delay(second, 1), second);
+        assert_eq!(delay(second, 4), Duration::from_secs(8));
+        assert_eq!(delay(second, 40), MAX_BACKOFF);
+        assert_eq!(delay(Duration::from_secs(u64::MAX), 2), MAX_BACKOFF);
+        assert_eq!(delay(Duration::from_secs(1 << 40), 1), MAX_BACKOFF);
+    }
+    #[test]
+    fn notify_changes() {
+        // 最初の要求には500を返し、再送させる
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", server.server_addr());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (i, mut request) in server.incoming_requests().enumerate() {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                tx.send(body).unwrap();
+                let status = if i == 0 { 500 } else { 200 };
+                request
+                    .respond(Response::from_string("").with_status_code(status))
+                    .unwrap();
+            }
+        });
+        let mut hook = Webhook::new(
+            "custom".to_string(),
+            url,
+            Kind::Json(r#"{{id}} {{change}}"#.to_string()),
+        );
+        hook.backoff = Duration::from_millis(10);
+        let hooks = Webhooks::new(vec![hook]);
+        let client = Client::builder().no_proxy().build().unwrap();
+        let mut state = State::default();
+
+        // 初回は記録だけ
+        let errors = hooks.notify(&client, &[article("1", "a")], &mut state);
+        assert!(errors.is_empty());
+        assert_eq!(state.sent.len(), 2);
+        assert!(rx.try_recv().is_err());
+
+        let articles = [article("2", "b"), article("1", "changed")];
+        assert!(hooks.notify(&client, &articles, &mut state).is_empty());
+        let received: Vec<String> = rx.try_iter().collect();
+        assert_eq!(received, ["1 changed", "1 changed", "2 new"]);
+
+        // 再実行しても重複して通知しない
+        assert!(hooks.notify(&client, &articles, &mut state).is_empty());
+        assert!(rx.try_recv().is_err());
+    }
+    #[test]
+    fn notify_failure() {
+        let mut hook = Webhook::new(
+            "down".to_string(),
+            "http://127.0.0.1:1/hook".to_string(),
+            Kind::Slack,
+        );
+        hook.backoff = Duration::from_millis(1);
+        let hooks = Webhooks::new(vec![hook]);
+        let client = Client::builder().no_proxy().build().unwrap();
+        let mut state = State::default();
+        state
+            .sent
+            .insert(("down".to_string(), "0".to_string()), String::new());
+        let errors = hooks.notify(&client, &[article("1", "a")], &mut state);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("down: "));
+        // 初回にお知らせがなくても、2回目からは通知する
+        let mut state = State::default();
+        assert!(hooks.notify(&client, &[], &mut state).is_empty());
+        assert_eq!(
+            hooks
+                .notify(&client, &[article("1", "a")], &mut state)
+                .len(),
+            1
+        );
+        // 送れなかったものは次回また通知する
+        assert!(!state
+            .sent
+            .contains_key(&("down".to_string(), "1".to_string())));
+    }
+}
@@ -0,0 +1,93 @@
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+
+const VOID: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+// XMLで定義済みの実体参照以外は数値文字参照にする
+// 空白や制御用の文字 (&nbsp;や&shy;など) は見分けが付くように数値文字参照にし、それ以外はそのまま出力する
+fn escape(s: &str, ret: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => *ret += "&amp;",
+            '<' => *ret += "&lt;",
+            '>' => *ret += "&gt;",
+            '"' => *ret += "&quot;",
+            c if !c.is_ascii()
+                && (c.is_whitespace()
+                    || matches!(
+                        c,
+                        '\u{ad}' | '\u{200b}'..='\u{200f}' | '\u{2060}' | '\u{feff}'
+                    )) =>
+            {
+                *ret += &format!("&#{};", c as u32)
+            }
+            // XML 1.0で使えない制御文字
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => ret.push(c),
+        }
+    }
+}
+
+fn write_node(node: NodeRef<Node>, ret: &mut String) {
+    match node.value() {
+        Node::Text(t) => escape(t, ret),
+        Node::Element(e) => {
+            let name = e.name();
+            *ret += "<";
+            *ret += name;
+            for (key, value) in e.attrs() {
+                if !is_xml_name(key) {
+                    continue;
+                }
+                *ret += &format!(" {}=\"", key);
+                escape(value, ret);
+                *ret += "\"";
+            }
+            if VOID.contains(&name) {
+                *ret += "/>";
+                return;
+            }
+            *ret += ">";
+            for child in node.children() {
+                write_node(child, ret);
+            }
+            *ret += &format!("</{}>", name);
+        }
+        _ => {}
+    }
+}
+
+// HTMLの断片を、Atomのtype="xhtml"に埋め込める整形式のXHTMLに変換する
+pub fn from_html(body: &str) -> String {
+    let html = Html::parse_fragment(body);
+    let mut ret = String::with_capacity(body.len());
+    for child in html.root_element().children() {
+        write_node(child, &mut ret);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn convert() {
+        assert_eq!(
+            from_html(
+                r#"<div>Windows&nbsp;11 &amp; 10 &copy; &lt;KB&gt;<br><img src="/a.png" alt="&quot;x&quot;"><p>unclosed<li>item</div><!-- comment -->"#
+            ),
+            r#"<div>Windows&#160;11 &amp; 10 © &lt;KB&gt;<br/><img alt="&quot;x&quot;" src="/a.png"/><p>unclosed</p><li>item</li></div>"#
+        );
+    }
+}
@@ -0,0 +1,65 @@
+// 記録したメッセージセンターのページを、ネットワークを使わずにAtomフィードにする
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn recordings() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay")
+}
+
+fn build(replay: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_unofficial-windows-message-center-rss"))
+        .args(["https://example.com/atom.xml", "--replay"])
+        .arg(replay)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn replay_recorded_page() {
+    let output = build(&recordings());
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let feed = String::from_utf8(output.stdout).unwrap();
+    assert!(feed.starts_with(r#"<?xml version="1.0" encoding="utf-8"?>"#));
+    assert!(feed.contains(
+        r#"<link href="https://example.com/atom.xml" rel="self" type="application/atom+xml" />"#
+    ));
+    assert_eq!(feed.matches("<entry>").count(), 2);
+    assert!(feed.contains(
+        "<id>https://learn.microsoft.com/en-us/windows/release-health/windows-message-center#3110</id>"
+    ));
+    assert!(feed.contains(
+        r#"<title type="html">Take action: June 2023 security update is now available</title>"#
+    ));
+    assert!(feed
+        .contains(r#"<link href="https://support.microsoft.com/help/5027231" rel="alternate" />"#));
+    assert!(feed.contains("<updated>2023-06-13T17:00:00Z</updated>"));
+    // ページ内のリンクしかないお知らせは、フィード自身を指す
+    assert!(feed.contains(r#"<link href="https://example.com/atom.xml" rel="self" />"#));
+    assert!(feed.contains("<updated>2023-06-09T21:00:00Z</updated>"));
+    // 分類とメタデータ、サニタイズした本文
+    assert!(feed.contains(r#"<category term="take-action""#));
+    assert!(feed.contains("<wmc:kb>5027231</wmc:kb>"));
+    assert!(feed.contains(r#"title="CVE-2022-38023""#));
+    assert!(!feed.contains("data-linktype"));
+}
+
+#[test]
+fn replay_error_status() {
+    let dir = tempfile::tempdir().unwrap();
+    for entry in fs::read_dir(recordings()).unwrap() {
+        let path = entry.unwrap().path();
+        let recording = fs::read_to_string(&path)
+            .unwrap()
+            .replace("Status: 200", "Status: 503");
+        fs::write(dir.path().join(path.file_name().unwrap()), recording).unwrap();
+    }
+    let output = build(dir.path());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("HTTP 503 from https://learn.microsoft.com/"));
+}